@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS logs (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    data JSONB NOT NULL,
+    created TIMESTAMPTZ NOT NULL
+);
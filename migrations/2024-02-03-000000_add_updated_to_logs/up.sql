@@ -0,0 +1 @@
+ALTER TABLE logs ADD COLUMN updated TIMESTAMPTZ;
@@ -0,0 +1 @@
+ALTER TABLE logs ADD COLUMN deleted_at TIMESTAMPTZ;
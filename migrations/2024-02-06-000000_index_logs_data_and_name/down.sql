@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS logs_name_created_idx;
+DROP INDEX IF EXISTS logs_data_gin_idx;
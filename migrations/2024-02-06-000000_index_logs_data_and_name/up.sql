@@ -0,0 +1,2 @@
+CREATE INDEX IF NOT EXISTS logs_data_gin_idx ON logs USING gin (data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS logs_name_created_idx ON logs (name, created);
@@ -0,0 +1,2 @@
+ALTER TABLE logs ADD COLUMN hash TEXT;
+CREATE INDEX IF NOT EXISTS logs_tenant_hash_created_idx ON logs (tenant, hash, created) WHERE hash IS NOT NULL;
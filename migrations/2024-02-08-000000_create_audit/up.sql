@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS audit (
+    id UUID PRIMARY KEY,
+    operation TEXT NOT NULL,
+    tenant TEXT,
+    key_hash TEXT,
+    affected BIGINT NOT NULL,
+    ids JSONB NOT NULL,
+    created TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audit_tenant_created_idx ON audit (tenant, created);
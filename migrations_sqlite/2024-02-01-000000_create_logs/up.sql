@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS logs (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created TEXT NOT NULL,
+    level TEXT NOT NULL DEFAULT 'info',
+    updated TEXT
+);
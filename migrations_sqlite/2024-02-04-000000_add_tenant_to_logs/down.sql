@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS logs_tenant_created_idx;
+ALTER TABLE logs DROP COLUMN tenant;
@@ -0,0 +1,2 @@
+ALTER TABLE logs ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default';
+CREATE INDEX IF NOT EXISTS logs_tenant_created_idx ON logs (tenant, created);
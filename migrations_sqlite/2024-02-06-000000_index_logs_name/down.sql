@@ -0,0 +1 @@
+DROP INDEX IF EXISTS logs_name_created_idx;
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS logs_name_created_idx ON logs (name, created);
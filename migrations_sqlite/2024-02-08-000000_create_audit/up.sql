@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS audit (
+    id TEXT PRIMARY KEY NOT NULL,
+    operation TEXT NOT NULL,
+    tenant TEXT,
+    key_hash TEXT,
+    affected BIGINT NOT NULL,
+    ids TEXT NOT NULL,
+    created TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audit_tenant_created_idx ON audit (tenant, created);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS logs_tenant_name_created_idx;
@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS logs_tenant_name_created_idx ON logs (tenant, name, created DESC, id DESC) WHERE deleted_at IS NULL;
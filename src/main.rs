@@ -1,48 +1,661 @@
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::PathRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::rejection::StringRejection;
+use axum::extract::ConnectInfo;
+use axum::extract::DefaultBodyLimit;
+use axum::extract::Path;
 use axum::extract::Query;
+use axum::extract::Request;
 use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::HeaderName;
+use axum::http::HeaderValue;
+use axum::http::Method;
 use axum::http::StatusCode;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::sse::Event;
+use axum::response::sse::KeepAlive;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::response::Sse;
+use axum::routing::delete;
 use axum::routing::get;
 use axum::routing::post;
+use axum::Extension;
 use axum::Json;
 use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use deadpool::managed::BuildError;
+use deadpool::managed::Hook;
+use deadpool::managed::HookError;
 use deadpool_diesel::postgres::Manager;
+use deadpool_diesel::postgres::Object;
 use deadpool_diesel::postgres::Pool;
+use deadpool_diesel::ManagerConfig;
+use deadpool_diesel::RecyclingMethod;
+use diesel::dsl::count_star;
+use diesel::dsl::sql;
+use diesel::dsl::AsSelect;
+use diesel::dsl::SqlTypeOf;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamptz;
 use diesel::table;
+use diesel_migrations::embed_migrations;
+use diesel_migrations::EmbeddedMigrations;
+use diesel_migrations::MigrationHarness;
 use dotenv::dotenv;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::Hmac;
+use hmac::Mac;
+use jsonschema::JSONSchema;
+use prometheus::Encoder;
+use prometheus::Histogram;
+use prometheus::HistogramOpts;
+use prometheus::IntCounter;
+use prometheus::Registry;
+use prometheus::TextEncoder;
 use serde::Deserialize;
+use serde::Serialize;
 use serde_json::json;
-use std::borrow::BorrowMut;
-use std::cell::RefCell;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::env;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tokio::sync::watch;
+use tokio_postgres::tls::NoTlsStream;
+use tokio_postgres::AsyncMessage;
+use tokio_postgres::NoTls;
+use tokio_postgres::Socket;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::AllowOrigin;
+use tower_http::cors::CorsLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing::error;
+use tracing::info;
+use tracing::info_span;
+use tracing::warn;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use utoipa::openapi::security::HttpAuthScheme;
+use utoipa::openapi::security::HttpBuilder;
+use utoipa::openapi::security::SecurityScheme;
+use utoipa::IntoParams;
+use utoipa::Modify;
+use utoipa::OpenApi;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+const SCHEMA_MISMATCH: &str = "logs table schema mismatch or missing, run migrations";
+
 const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_LOG_FILTER: &str = "logger_rs=info";
+const DEFAULT_LIMIT: i64 = 100;
+const DEFAULT_MAX_ALL_ROWS: i64 = 100_000;
+const DEFAULT_POOL_SIZE: usize = 4;
+const MAX_BATCH_SIZE: usize = 1000;
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+const MAX_DATA_DEPTH: usize = 32;
+const MAX_DATA_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_BATCH_BODY_BYTES: usize = 16 * 1024 * 1024;
+const DEFAULT_MAX_NAME_LENGTH: usize = 255;
+const DEFAULT_LEVEL: &str = "info";
+const LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+const STREAM_BUFFER: usize = 64;
+const STREAM_CHUNK: i64 = 500;
+const LIVE_BUFFER: usize = 1024;
+const WEBHOOK_BUFFER: usize = 1024;
+const WEBHOOK_ATTEMPTS: u32 = 4;
+const WEBHOOK_BACKOFF: Duration = Duration::from_millis(500);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(feature = "sqlite")]
+const DEFAULT_SQLITE_PATH: &str = "logs.db";
+const LIVE_CHANNEL: &str = "new_log";
+const DEFAULT_CONNECT_RETRIES: u32 = 10;
+const CONNECT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+const CONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+const POOL_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_millis(5000);
+const DEFAULT_RETENTION_SWEEP: Duration = Duration::from_secs(3600);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+const UNAVAILABLE_RETRY_AFTER: &str = "1";
+const API_PREFIX: &str = "/v1";
+const DEPRECATION_HEADER: &str = "deprecation";
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+const RESUME_TOKEN_HEADER: &str = "x-resume-token";
+const LOG_FIELDS: [&str; 6] = ["id", "name", "data", "created", "level", "updated"];
+const DEFAULT_RATE_LIMIT_PER_MIN: usize = 600;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+const RATE_LIMIT_MAX_TRACKED: usize = 10_000;
+const DEFAULT_TENANT: &str = "default";
+const DEFAULT_KEY: &str = "x";
+const MIN_KEY_LENGTH: usize = 16;
+const DEFAULT_TEXT_NAME: &str = "text";
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const SETTINGS: [&str; 38] = [
+    "APP_ENV",
+    "READ_ONLY",
+    "HOST",
+    "PORT",
+    "KEY",
+    "KEYS",
+    "TENANT_KEYS",
+    "LIMIT",
+    "MAX_ALL_ROWS",
+    "COMPRESS_THRESHOLD_BYTES",
+    "RATE_LIMIT_PER_MIN",
+    "QUOTAS",
+    "QUOTA_ACTION",
+    "MAX_NAME_LENGTH",
+    "MAX_BODY_BYTES",
+    "MAX_BATCH_BODY_BYTES",
+    "DB_BACKEND",
+    "SQLITE_PATH",
+    "DB_USER",
+    "DB_PASSWORD",
+    "DB_HOST",
+    "DB_PORT",
+    "DB_NAME",
+    "DB_POOL_SIZE",
+    "DB_CONNECT_RETRIES",
+    "DB_QUERY_TIMEOUT_MS",
+    "RETENTION_DAYS",
+    "RETENTION_SWEEP_SECS",
+    "HMAC_SECRET",
+    "HMAC_MAX_AGE_SECS",
+    "WEBHOOK_URL",
+    "WEBHOOK_SECRET",
+    "DEDUPE_WINDOW_SECS",
+    "TLS_CERT_PATH",
+    "TLS_KEY_PATH",
+    "CORS_ORIGINS",
+    "SCHEMAS_DIR",
+    "RUST_LOG",
+];
+const RESTART_SETTINGS: [&str; 24] = [
+    "HOST",
+    "PORT",
+    "READ_ONLY",
+    "COMPRESS_THRESHOLD_BYTES",
+    "MAX_BODY_BYTES",
+    "MAX_BATCH_BODY_BYTES",
+    "DB_BACKEND",
+    "SQLITE_PATH",
+    "DB_USER",
+    "DB_PASSWORD",
+    "DB_HOST",
+    "DB_PORT",
+    "DB_NAME",
+    "DB_POOL_SIZE",
+    "DB_CONNECT_RETRIES",
+    "DB_QUERY_TIMEOUT_MS",
+    "RETENTION_DAYS",
+    "RETENTION_SWEEP_SECS",
+    "WEBHOOK_URL",
+    "WEBHOOK_SECRET",
+    "TLS_CERT_PATH",
+    "TLS_KEY_PATH",
+    "CORS_ORIGINS",
+    "RUST_LOG",
+];
+const SECRET_SETTINGS: [&str; 7] = [
+    "KEY",
+    "KEYS",
+    "TENANT_KEYS",
+    "DB_PASSWORD",
+    "HMAC_SECRET",
+    "WEBHOOK_URL",
+    "WEBHOOK_SECRET",
+];
+const SIGNATURE_HEADER: &str = "x-signature";
+const TIMESTAMP_HEADER: &str = "x-timestamp";
+const DEFAULT_SIGNATURE_MAX_AGE: Duration = Duration::from_secs(300);
+const MAX_CREATED_SKEW: Duration = Duration::from_secs(300);
+const PREFER_HEADER: &str = "prefer";
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(3600);
+const IDEMPOTENCY_MAX_TRACKED: usize = 10_000;
+const DEFAULT_DEDUPE_WINDOW: Duration = Duration::from_secs(300);
+const AUDIT_MAX_IDS: usize = 1000;
 
-#[derive(Clone)]
-struct EnvVars {
-    key: Arc<String>,
+struct Config {
+    addr: String,
+    keys: HashMap<String, String>,
+    admin_keys: HashSet<String>,
+    weak_keys: bool,
+    read_only: bool,
     limit: i64,
+    max_all_rows: i64,
+    compress_threshold: Option<usize>,
+    rate_limit_per_min: usize,
+    quotas: HashMap<String, i64>,
+    quota_action: QuotaAction,
+    max_name_length: usize,
+    query_timeout: Duration,
+    max_body_bytes: usize,
+    max_batch_body_bytes: usize,
+    pool_size: usize,
+    connect_retries: u32,
+    retention_days: Option<i64>,
+    retention_sweep: Duration,
+    cors_origins: Option<AllowOrigin>,
+    schemas: HashMap<String, JSONSchema>,
+    hmac_secret: Option<Vec<u8>>,
+    signature_max_age: Duration,
+    webhook_url: Option<reqwest::Url>,
+    webhook_secret: Option<Vec<u8>>,
+    dedupe_window: Duration,
 }
 
-thread_local! {
-    static VARS: RefCell<EnvVars> = RefCell::new(EnvVars {
-        key: Arc::new(std::env::var("KEY").unwrap_or("x".to_string())),
-        limit: std::env::var("LIMIT").map(|l| l.parse::<i64>().ok()).unwrap_or(Some(100)).unwrap(),
-    });
+#[derive(Debug)]
+struct ConfigError(Vec<String>);
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration:")?;
+
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    fn from_env(settings: &Settings) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+
+        let host = settings.var("HOST").unwrap_or("localhost".to_string());
+        let port = settings
+            .parsed("PORT", parse_port, &mut errors)
+            .unwrap_or(DEFAULT_PORT);
+        let addr = bind_address(&host, port).unwrap_or_else(|err| {
+            errors.push(err);
+            String::new()
+        });
+
+        let mut keys = match settings.var("KEYS") {
+            Ok(value) => parse_keys(&value),
+            Err(_) => {
+                let key = settings.var("KEY").unwrap_or(DEFAULT_KEY.to_string());
+
+                if key.is_empty() {
+                    errors.push("Invalid KEY value \"\", expected a non-empty key".to_string());
+                }
+
+                HashMap::from([(key, DEFAULT_TENANT.to_string())])
+            }
+        };
+
+        let mut admin_keys = keys.keys().cloned().collect::<HashSet<_>>();
+
+        if let Some(tenant_keys) = settings.parsed("TENANT_KEYS", parse_tenant_keys, &mut errors) {
+            for key in tenant_keys.keys() {
+                admin_keys.remove(key);
+            }
+
+            keys.extend(tenant_keys);
+        }
+
+        let weak_keys = keys
+            .keys()
+            .any(|key| key == DEFAULT_KEY || key.len() < MIN_KEY_LENGTH);
+
+        if weak_keys && settings.var("APP_ENV").is_ok_and(|env| env == "production") {
+            errors.push(format!(
+                "A key is left at the insecure default \"{DEFAULT_KEY}\" or shorter than {MIN_KEY_LENGTH} characters, set longer keys when APP_ENV=production"
+            ));
+        }
+
+        let read_only = settings
+            .parsed("READ_ONLY", parse_read_only, &mut errors)
+            .unwrap_or(false);
+
+        let max_all_rows = settings
+            .parsed("MAX_ALL_ROWS", parse_max_all_rows, &mut errors)
+            .unwrap_or(DEFAULT_MAX_ALL_ROWS);
+        let mut limit = settings
+            .parsed("LIMIT", parse_limit, &mut errors)
+            .unwrap_or(DEFAULT_LIMIT);
+
+        if limit > max_all_rows {
+            warn!("LIMIT {limit} exceeds MAX_ALL_ROWS, clamping to {max_all_rows}");
+            limit = max_all_rows;
+        }
+        let compress_threshold = settings.parsed(
+            "COMPRESS_THRESHOLD_BYTES",
+            parse_compress_threshold,
+            &mut errors,
+        );
+        let rate_limit_per_min = settings
+            .parsed(
+                "RATE_LIMIT_PER_MIN",
+                |value| parse_count("RATE_LIMIT_PER_MIN", value),
+                &mut errors,
+            )
+            .unwrap_or(DEFAULT_RATE_LIMIT_PER_MIN);
+        let quotas = settings
+            .parsed("QUOTAS", parse_quotas, &mut errors)
+            .unwrap_or_default();
+        let quota_action = settings
+            .parsed("QUOTA_ACTION", parse_quota_action, &mut errors)
+            .unwrap_or(QuotaAction::Reject);
+        let max_name_length = settings
+            .parsed(
+                "MAX_NAME_LENGTH",
+                |value| parse_count("MAX_NAME_LENGTH", value),
+                &mut errors,
+            )
+            .unwrap_or(DEFAULT_MAX_NAME_LENGTH);
+        let max_body_bytes = settings
+            .parsed(
+                "MAX_BODY_BYTES",
+                |value| parse_size("MAX_BODY_BYTES", value),
+                &mut errors,
+            )
+            .unwrap_or(DEFAULT_MAX_BODY_BYTES);
+        let max_batch_body_bytes = settings
+            .parsed(
+                "MAX_BATCH_BODY_BYTES",
+                |value| parse_size("MAX_BATCH_BODY_BYTES", value),
+                &mut errors,
+            )
+            .unwrap_or(DEFAULT_MAX_BATCH_BODY_BYTES);
+
+        let pool_size = settings
+            .parsed("DB_POOL_SIZE", parse_pool_size, &mut errors)
+            .unwrap_or(DEFAULT_POOL_SIZE);
+        let connect_retries = settings
+            .parsed("DB_CONNECT_RETRIES", parse_connect_retries, &mut errors)
+            .unwrap_or(DEFAULT_CONNECT_RETRIES);
+        let query_timeout = settings
+            .parsed("DB_QUERY_TIMEOUT_MS", parse_query_timeout, &mut errors)
+            .unwrap_or(DEFAULT_QUERY_TIMEOUT);
+
+        let retention_days = settings.parsed("RETENTION_DAYS", parse_retention_days, &mut errors);
+        let retention_sweep = settings
+            .parsed("RETENTION_SWEEP_SECS", parse_retention_sweep, &mut errors)
+            .unwrap_or(DEFAULT_RETENTION_SWEEP);
+
+        let hmac_secret = settings.var("HMAC_SECRET").ok().map(String::into_bytes);
+        let signature_max_age = settings
+            .parsed("HMAC_MAX_AGE_SECS", parse_signature_max_age, &mut errors)
+            .unwrap_or(DEFAULT_SIGNATURE_MAX_AGE);
+        let webhook_url = settings.parsed("WEBHOOK_URL", parse_webhook_url, &mut errors);
+        let webhook_secret = settings.var("WEBHOOK_SECRET").ok().map(String::into_bytes);
+
+        let dedupe_window = settings
+            .parsed("DEDUPE_WINDOW_SECS", parse_dedupe_window, &mut errors)
+            .unwrap_or(DEFAULT_DEDUPE_WINDOW);
+
+        let cors_origins = settings.parsed("CORS_ORIGINS", parse_cors_origins, &mut errors);
+
+        let schemas = settings
+            .parsed(
+                "SCHEMAS_DIR",
+                |dir| {
+                    let schemas = load_schemas(dir)
+                        .map_err(|err| format!("Could not load schemas from \"{dir}\": {err}"))?;
+
+                    info!("Loaded {} data schemas from {dir}", schemas.len());
+
+                    Ok(schemas)
+                },
+                &mut errors,
+            )
+            .unwrap_or_default();
+
+        if !errors.is_empty() {
+            return Err(ConfigError(errors));
+        }
+
+        Ok(Config {
+            addr,
+            keys,
+            admin_keys,
+            weak_keys,
+            read_only,
+            limit,
+            max_all_rows,
+            compress_threshold,
+            rate_limit_per_min,
+            quotas,
+            quota_action,
+            max_name_length,
+            query_timeout,
+            max_body_bytes,
+            max_batch_body_bytes,
+            pool_size,
+            connect_retries,
+            retention_days,
+            retention_sweep,
+            cors_origins,
+            schemas,
+            hmac_secret,
+            signature_max_age,
+            webhook_url,
+            webhook_secret,
+            dedupe_window,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum QuotaAction {
+    Reject,
+    Evict,
 }
 
 struct AppState {
-    db: Arc<Pool>,
+    store: Arc<dyn LogStore>,
+    config: RwLock<Arc<Config>>,
+    settings: Settings,
+    rate_limits: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+    idempotency: Mutex<HashMap<String, (Instant, Option<serde_json::Value>)>>,
+    live: broadcast::Sender<(String, serde_json::Value)>,
+    shutdown: watch::Sender<bool>,
+    metrics: Metrics,
+    start_time: Instant,
+    read_only: AtomicBool,
+    webhook: Option<tokio::sync::mpsc::Sender<serde_json::Value>>,
+}
+
+impl AppState {
+    fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
 }
 
+struct Metrics {
+    registry: Registry,
+    logs_ingested: IntCounter,
+    giveme_requests: IntCounter,
+    auth_failures: IntCounter,
+    request_duration: Histogram,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let logs_ingested = IntCounter::new("logs_ingested_total", "Number of logs stored")?;
+        let giveme_requests =
+            IntCounter::new("giveme_requests_total", "Number of /giveme requests")?;
+        let auth_failures = IntCounter::new("auth_failures_total", "Number of rejected API keys")?;
+        let request_duration = Histogram::with_opts(HistogramOpts::new(
+            "http_request_duration_seconds",
+            "HTTP request latency in seconds",
+        ))?;
+
+        registry.register(Box::new(logs_ingested.clone()))?;
+        registry.register(Box::new(giveme_requests.clone()))?;
+        registry.register(Box::new(auth_failures.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            logs_ingested,
+            giveme_requests,
+            auth_failures,
+            request_duration,
+        })
+    }
+}
+
+type BoxedLogs = logs::BoxedQuery<'static, Pg, SqlTypeOf<AsSelect<Log, Pg>>>;
+
 table! {
     logs (id) {
         id -> Uuid,
         name -> Text,
         data -> Jsonb,
         created -> Timestamptz,
+        level -> Text,
+        updated -> Nullable<Timestamptz>,
+        tenant -> Text,
+        deleted_at -> Nullable<Timestamptz>,
+        hash -> Nullable<Text>,
+        data_compressed -> Nullable<Bytea>,
+    }
+}
+
+table! {
+    audit (id) {
+        id -> Uuid,
+        operation -> Text,
+        tenant -> Nullable<Text>,
+        key_hash -> Nullable<Text>,
+        affected -> BigInt,
+        ids -> Jsonb,
+        created -> Timestamptz,
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+struct Settings {
+    file: HashMap<String, String>,
+}
+
+impl Settings {
+    fn load() -> Result<Self, String> {
+        let (path, required) = match env::var("CONFIG_PATH") {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_PATH.to_string(), false),
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Settings {
+                    file: HashMap::new(),
+                });
+            }
+            Err(err) => return Err(format!("Could not read config file \"{path}\": {err}")),
+        };
+
+        let table = contents
+            .parse::<toml::Table>()
+            .map_err(|err| format!("Invalid config file \"{path}\": {err}"))?;
+        let mut file = HashMap::new();
+
+        for (key, value) in table {
+            let name = key.to_uppercase();
+
+            if !SETTINGS.contains(&name.as_str()) {
+                return Err(format!(
+                    "Unknown setting \"{key}\" in config file \"{path}\""
+                ));
+            }
+
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Array(values) => values
+                    .into_iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value,
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+                value => value.to_string(),
+            };
+
+            file.insert(name, value);
+        }
+
+        Ok(Settings { file })
+    }
+
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        env::var(name).or_else(|err| self.file.get(name).cloned().ok_or(err))
+    }
+
+    fn parsed<T>(
+        &self,
+        name: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+        errors: &mut Vec<String>,
+    ) -> Option<T> {
+        let value = self.var(name).ok()?;
+
+        parse(&value).map_err(|err| errors.push(err)).ok()
+    }
+
+    fn effective(&self) -> String {
+        SETTINGS
+            .iter()
+            .filter_map(|name| {
+                self.var(name).ok().map(|value| {
+                    if SECRET_SETTINGS.contains(name) {
+                        format!("{name}=<redacted>")
+                    } else {
+                        format!("{name}={value}")
+                    }
+                })
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 }
 
@@ -50,163 +663,4562 @@ table! {
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
 
-    if let Ok(value) = std::env::var("RUST_LOG") {
-        if value == "debug" {
-            env_logger::init();
-        }
-    }
+    let settings = Settings::load().map_err(anyhow::Error::msg)?;
 
-    let host = env::var("HOST").unwrap_or("localhost".to_string());
-    let port = env::var("PORT")
-        .map(|p| p.parse::<u16>().unwrap_or(DEFAULT_PORT))
-        .unwrap_or(DEFAULT_PORT);
+    init_tracing(&settings);
+
+    info!("Effective settings: {}", settings.effective());
+
+    let config = Config::from_env(&settings)?;
+    let addr = config.addr.clone();
+    let weak_keys = config.weak_keys;
+
+    let tls = match (settings.var("TLS_CERT_PATH"), settings.var("TLS_KEY_PATH")) {
+        (Ok(cert), Ok(key)) => match load_tls(&cert, &key).await {
+            Ok(tls) => Some(tls),
+            Err(err) => {
+                anyhow::bail!("Could not load TLS certificate \"{cert}\" and key \"{key}\": {err}")
+            }
+        },
+        (Err(_), Err(_)) => None,
+        _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+    };
 
     let connection = format!(
         "postgres://{}:{}@{}:{}/{}",
-        env::var("DB_USER").unwrap_or("postgres".to_string()),
-        env::var("DB_PASSWORD").unwrap_or("postgres".to_string()),
-        env::var("DB_HOST").unwrap_or("localhost".to_string()),
-        env::var("DB_PORT").unwrap_or("5432".to_string()),
-        env::var("DB_NAME").unwrap_or("postgres".to_string()),
+        settings.var("DB_USER").unwrap_or("postgres".to_string()),
+        settings
+            .var("DB_PASSWORD")
+            .unwrap_or("postgres".to_string()),
+        settings.var("DB_HOST").unwrap_or("localhost".to_string()),
+        settings.var("DB_PORT").unwrap_or("5432".to_string()),
+        settings.var("DB_NAME").unwrap_or("postgres".to_string()),
     );
 
-    println!("Connecting to database...");
+    let backend = settings.var("DB_BACKEND").unwrap_or("postgres".to_string());
+    let live = broadcast::channel(LIVE_BUFFER).0;
+
+    let (db, store): (Option<Arc<Pool>>, Arc<dyn LogStore>) = match backend.as_str() {
+        "postgres" => {
+            info!("Connecting to database...");
+
+            let db = match database_pool(&connection, &config) {
+                Ok(db) => Arc::new(db),
+                Err(err) => anyhow::bail!("Could not create database pool: {err}"),
+            };
+
+            if let Err(err) =
+                wait_for_database(&db, config.connect_retries, config.query_timeout).await
+            {
+                anyhow::bail!(
+                    "Could not connect to database after {} attempts: {err}",
+                    config.connect_retries
+                );
+            }
+
+            info!("Connected to database (pool size {})", config.pool_size);
+
+            match run_migrations(&db, config.query_timeout).await {
+                Ok(0) => info!("Database schema is up to date"),
+                Ok(applied) => info!("Applied {applied} database migrations"),
+                Err(err) => anyhow::bail!("Could not run database migrations: {err}"),
+            }
+
+            if let Err(err) = check_schema(&db, config.query_timeout).await {
+                anyhow::bail!("{SCHEMA_MISMATCH}: {err}");
+            }
+
+            let store = Arc::new(PgStore {
+                db: db.clone(),
+                query_timeout: config.query_timeout,
+                compress_threshold: config.compress_threshold,
+            });
+
+            (Some(db), store)
+        }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            let path = settings
+                .var("SQLITE_PATH")
+                .unwrap_or(DEFAULT_SQLITE_PATH.to_string());
+
+            let store = match sqlite::SqliteStore::open(
+                &path,
+                config.pool_size,
+                config.query_timeout,
+                config.compress_threshold,
+                live.clone(),
+            )
+            .await
+            {
+                Ok(store) => store,
+                Err(err) => anyhow::bail!("Could not open sqlite database \"{path}\": {err}"),
+            };
+
+            info!(
+                "Opened sqlite database {path} (pool size {})",
+                config.pool_size
+            );
+
+            (None, Arc::new(store))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => anyhow::bail!("DB_BACKEND=sqlite requires building with --features sqlite"),
+        _ => anyhow::bail!("Invalid DB_BACKEND value \"{backend}\", expected postgres or sqlite"),
+    };
+
+    let (shutdown, _) = watch::channel(false);
+
+    let read_only = AtomicBool::new(config.read_only);
+    let retention = config
+        .retention_days
+        .map(|days| (days, config.retention_sweep));
+    let mut background = Vec::new();
+    let webhook = config.webhook_url.clone().map(|url| {
+        let (queued, queue) = tokio::sync::mpsc::channel(WEBHOOK_BUFFER);
+
+        info!(
+            "Forwarding new logs to a webhook on {}",
+            url.host_str().unwrap_or_default()
+        );
+
+        background.push(tokio::spawn(forward_webhooks(
+            url,
+            config.webhook_secret.clone(),
+            queue,
+            shutdown.subscribe(),
+        )));
+
+        queued
+    });
+    let state = Arc::new(AppState {
+        store,
+        config: RwLock::new(Arc::new(config)),
+        settings,
+        rate_limits: Mutex::new(HashMap::new()),
+        idempotency: Mutex::new(HashMap::new()),
+        live,
+        shutdown,
+        metrics: Metrics::new()?,
+        start_time: Instant::now(),
+        read_only,
+        webhook,
+    });
+
+    if let Some((days, every)) = retention {
+        info!("Deleting logs older than {days} days every {every:?}");
+        background.push(tokio::spawn(retention_sweeps(state.clone(), days, every)));
+    }
+
+    if let Some(db) = &db {
+        let (notified, notifications) = tokio::sync::mpsc::channel(LIVE_BUFFER);
+        tokio::spawn(listen_for_logs(connection, notified));
+        tokio::spawn(republish_logs(state.clone(), db.clone(), notifications));
+    }
+
+    let app = build_app(state.clone());
+
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(err) => anyhow::bail!("Could not bind to {addr}: {err}"),
+    };
+
+    if weak_keys {
+        warn!("A key is left at the insecure default \"{DEFAULT_KEY}\" or shorter than {MIN_KEY_LENGTH} characters, anyone who guesses it can read your logs");
+    }
+
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let served = match tls {
+        Some(tls) => {
+            let handle = axum_server::Handle::new();
+
+            tokio::spawn({
+                let handle = handle.clone();
+
+                async move {
+                    shutdown_signal(state).await;
+                    handle.graceful_shutdown(None);
+                }
+            });
 
-    let manager = Manager::new(connection, deadpool_diesel::Runtime::Tokio1);
-    let db = Pool::builder(manager).max_size(4).build();
+            axum_server::from_tcp_rustls(listener.into_std()?, tls)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        None => {
+            axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown_signal(state))
+                .await
+        }
+    };
 
-    if let Err(err) = db {
-        eprintln!("Error: {err}");
+    if let Err(err) = served {
+        error!("Runtime: {err}");
         return Ok(());
     }
 
-    println!("Connected to database");
+    for task in background {
+        let _ = task.await;
+    }
+
+    if let Some(db) = db {
+        db.close();
+    }
 
-    let db = Arc::new(db.unwrap());
+    Ok(())
+}
 
-    let app = Router::new()
-        .route("/", get(index))
-        .route("/log", post(log))
+fn build_app(state: Arc<AppState>) -> Router {
+    let config = state.config();
+
+    let ingest = Router::new()
+        .route(
+            "/log",
+            post(log).layer(DefaultBodyLimit::max(config.max_body_bytes)),
+        )
+        .route(
+            "/log/batch",
+            post(log).layer(DefaultBodyLimit::max(config.max_batch_body_bytes)),
+        )
+        .route(
+            "/log/text",
+            post(log_text).layer(DefaultBodyLimit::max(config.max_body_bytes)),
+        )
+        .route_layer(RequestDecompressionLayer::new())
+        .route_layer(middleware::from_fn_with_state(state.clone(), reject_writes))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), identify))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            verify_signature,
+        ));
+
+    let writes = Router::new()
+        .route("/log/:id", delete(delete_log).patch(patch_log))
+        .route("/log/:id/restore", post(restore_log))
+        .route("/logs", delete(delete_logs))
+        .route("/logs/retention", delete(retention))
+        .route_layer(middleware::from_fn_with_state(state.clone(), reject_writes));
+
+    let admin = Router::new()
+        .route("/admin/readonly", post(set_read_only))
+        .route("/admin/reload", post(reload_config))
+        .route_layer(middleware::from_fn(require_admin));
+
+    let protected = Router::new()
+        .route("/log/:id", get(get_log))
+        .route("/log/:id/data", get(get_log_data))
         .route("/giveme", get(giveme))
-        .with_state(Arc::new(AppState { db }));
+        .route("/stream", get(stream))
+        .route("/query", post(query_logs))
+        .route("/logs/search", get(search))
+        .route("/count", get(count))
+        .route("/names", get(names))
+        .route("/latest", get(latest))
+        .route("/stats/by-name", get(stats_by_name))
+        .route("/stats/timeseries", get(stats_timeseries))
+        .route("/export.csv", get(export_csv))
+        .route("/export", get(export))
+        .route("/health/detail", get(health_detail))
+        .route("/audit", get(audit_log))
+        .merge(writes)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth));
+
+    let api = Router::new().merge(ingest).merge(protected);
+
+    let mut app = Router::new()
+        .route("/", get(index))
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .route("/openapi.json", get(openapi))
+        .nest(API_PREFIX, api.clone())
+        .merge(api.layer(middleware::from_fn(deprecated)))
+        .fallback(not_found)
+        .layer(middleware::from_fn(pretty_json))
+        .layer(CompressionLayer::new());
+
+    if let Some(origins) = config.cors_origins.clone() {
+        app = app
+            .layer(
+                CorsLayer::new()
+                    .allow_origin(origins)
+                    .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+                    .allow_headers([
+                        header::AUTHORIZATION,
+                        header::CONTENT_TYPE,
+                        HeaderName::from_static(IDEMPOTENCY_KEY),
+                        HeaderName::from_static(PREFER_HEADER),
+                        HeaderName::from_static(REQUEST_ID_HEADER),
+                        HeaderName::from_static(SIGNATURE_HEADER),
+                        HeaderName::from_static(TIMESTAMP_HEADER),
+                    ]),
+            )
+            .layer(middleware::from_fn(preflight_no_content));
+    }
+
+    app.layer(middleware::from_fn_with_state(state.clone(), trace_request))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyRequest {
+    key: Option<String>,
+}
 
-    let listener = tokio::net::TcpListener::bind(&format!("{host}:{port}"))
+async fn load_tls(cert: &str, key: &str) -> Result<RustlsConfig, String> {
+    let pem = tokio::fs::read_to_string(cert)
         .await
-        .unwrap();
+        .map_err(|err| err.to_string())?;
 
-    if let Err(err) = axum::serve(listener, app).await {
-        eprintln!("Runtime: {err}");
-        return Ok(());
+    if !pem.contains("-----BEGIN CERTIFICATE-----") {
+        return Err("expected a PEM encoded certificate".to_string());
     }
 
-    Ok(())
+    RustlsConfig::from_pem_file(cert, key)
+        .await
+        .map_err(|err| err.to_string())
 }
 
-#[derive(Identifiable, Queryable, Selectable, Eq, PartialEq)]
-#[diesel(table_name = logs)]
-struct Log {
-    id: Uuid,
-    name: String,
-    data: serde_json::Value,
-    created: chrono::DateTime<chrono::Utc>,
+#[derive(Clone)]
+struct Scan {
+    query: LogQuery,
+    after: Option<Position>,
+    through: Option<Position>,
+    ascending: bool,
 }
 
-#[derive(Deserialize)]
-struct StrippedLog {
-    name: String,
-    data: serde_json::Value,
+#[derive(Default)]
+struct Inserted {
+    logs: Vec<Log>,
+    evicted: Vec<(String, Vec<Uuid>)>,
 }
 
-async fn index() -> Json<serde_json::Value> {
-    Json(json!({
-        "message": ":)"
-    }))
+#[derive(Clone, Copy)]
+enum Interval {
+    Hour,
+    Day,
 }
 
-async fn log(
-    State(state): State<Arc<AppState>>,
-    Json(req_body): Json<StrippedLog>,
-) -> Json<serde_json::Value> {
-    use logs::dsl;
-
-    let conn = state.db.clone().borrow_mut().get().await.unwrap();
-    let query = conn
-        .interact(move |conn| {
-            diesel::insert_into(dsl::logs)
-                .values((
-                    dsl::id.eq(Uuid::new_v4()),
-                    dsl::name.eq(req_body.name.clone()),
-                    dsl::data.eq(json!(req_body.data)),
-                    dsl::created.eq(chrono::Utc::now()),
-                ))
-                .execute(conn)
-        })
-        .await;
+#[async_trait]
+trait LogStore: Send + Sync {
+    async fn ping(&self) -> Result<(), QueryError>;
 
-    if let Err(err) = query {
-        eprintln!("Error: {err}");
-        return Json(
-            json!({ "status": StatusCode::INTERNAL_SERVER_ERROR.as_u16(), "message": "Could not log data" }),
-        );
-    }
+    fn status(&self) -> deadpool::Status;
+
+    async fn insert(
+        &self,
+        rows: Vec<Log>,
+        quotas: BTreeMap<String, (i64, i64)>,
+        action: QuotaAction,
+    ) -> Result<Inserted, QueryError>;
+
+    async fn query(
+        &self,
+        caller: String,
+        query: LogQuery,
+        limit: i64,
+        ascending: bool,
+    ) -> Result<Vec<Log>, QueryError>;
+
+    async fn count(&self, caller: String, query: LogQuery) -> Result<i64, QueryError>;
+
+    async fn names(&self, caller: String) -> Result<Vec<String>, QueryError>;
 
-    Json(json!({ "status": 200, "message": "OK" }))
+    async fn scan(
+        &self,
+        caller: String,
+        scan: Scan,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Log>, QueryError>;
+
+    async fn latest(&self, caller: String, name: Option<String>) -> Result<Vec<Log>, QueryError>;
+
+    async fn counts_by_name(
+        &self,
+        caller: String,
+        created_after: Option<chrono::DateTime<chrono::Utc>>,
+        created_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<(String, i64)>, QueryError>;
+
+    async fn timeseries(
+        &self,
+        caller: String,
+        interval: Interval,
+        created_after: chrono::DateTime<chrono::Utc>,
+        created_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(chrono::DateTime<chrono::Utc>, i64)>, QueryError>;
+
+    async fn delete_older_than(
+        &self,
+        caller: Option<String>,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, QueryError>;
+
+    async fn find_duplicates(
+        &self,
+        caller: String,
+        hashes: Vec<String>,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Log>, QueryError>;
+
+    async fn get(&self, caller: String, id: Uuid) -> Result<Option<Log>, QueryError>;
+
+    async fn update(
+        &self,
+        caller: String,
+        id: Uuid,
+        name: Option<String>,
+        data: Option<serde_json::Value>,
+    ) -> Result<Option<Log>, QueryError>;
+
+    async fn delete(&self, caller: String, id: Uuid, hard: bool) -> Result<usize, QueryError>;
+
+    async fn delete_named(
+        &self,
+        caller: String,
+        name: String,
+        hard: bool,
+    ) -> Result<Vec<Uuid>, QueryError>;
+
+    async fn restore(&self, caller: String, id: Uuid) -> Result<usize, QueryError>;
+
+    async fn record_audit(&self, entry: AuditEntry) -> Result<(), QueryError>;
+
+    async fn audit(
+        &self,
+        caller: String,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, QueryError>;
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GivemeRequest {
-    key: Option<String>,
-    all: Option<bool>,
+struct PgStore {
+    db: Arc<Pool>,
+    query_timeout: Duration,
+    compress_threshold: Option<usize>,
 }
 
-async fn giveme(
-    Query(query): Query<GivemeRequest>,
-    State(state): State<Arc<AppState>>,
-) -> Json<serde_json::Value> {
-    use self::logs::dsl::*;
+impl PgStore {
+    async fn conn(&self) -> Result<Object, QueryError> {
+        self.db
+            .get()
+            .await
+            .map_err(|err| QueryError::Unavailable(err.to_string()))
+    }
+}
 
-    let EnvVars { key, limit } = VARS.with(|vars| vars.borrow().clone());
+#[async_trait]
+impl LogStore for PgStore {
+    async fn ping(&self) -> Result<(), QueryError> {
+        ping_database(&self.db, self.query_timeout)
+            .await
+            .map_err(QueryError::Unavailable)
+    }
 
-    if query.key.is_none() || query.key.as_ref().unwrap() != key.as_str() {
-        return Json(
-            json!({ "status": StatusCode::UNAUTHORIZED.as_u16(), "message": "Unauthorized" }),
-        );
+    fn status(&self) -> deadpool::Status {
+        self.db.status()
     }
 
-    let query = state
-        .db
-        .clone()
-        .borrow_mut()
-        .get()
-        .await
-        .unwrap()
-        .interact(move |conn| {
-            if query.all.unwrap_or(false) {
-                logs.select(Log::as_select()).load(conn)
+    async fn insert(
+        &self,
+        rows: Vec<Log>,
+        quotas: BTreeMap<String, (i64, i64)>,
+        action: QuotaAction,
+    ) -> Result<Inserted, QueryError> {
+        let conn = self.conn().await?;
+        let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+        let tenant = rows
+            .first()
+            .map(|row| row.tenant.clone())
+            .unwrap_or_default();
+        let rows = rows
+            .iter()
+            .map(|row| row.stored(self.compress_threshold))
+            .collect::<Vec<_>>();
+
+        interact(conn, self.query_timeout, move |conn| {
+            conn.transaction::<_, QueryError, _>(|conn| {
+                for name in quotas.keys() {
+                    diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1), hashtext($2))")
+                        .bind::<Text, _>(&tenant)
+                        .bind::<Text, _>(name)
+                        .execute(conn)?;
+                }
+
+                if action == QuotaAction::Reject {
+                    for (name, (quota, incoming)) in &quotas {
+                        let stored = logs::table
+                            .filter(logs::tenant.eq(&tenant))
+                            .filter(logs::name.eq(name))
+                            .count()
+                            .get_result::<i64>(conn)?;
+
+                        if stored + incoming > *quota {
+                            return Err(QueryError::OverQuota(name.clone(), *quota));
+                        }
+                    }
+                }
+
+                let inserted = diesel::insert_into(logs::table)
+                    .values(&rows)
+                    .returning(Log::as_returning())
+                    .get_results(conn)?;
+
+                diesel::sql_query("SELECT pg_notify($1, id::text) FROM unnest($2) AS id")
+                    .bind::<Text, _>(LIVE_CHANNEL)
+                    .bind::<Array<diesel::sql_types::Uuid>, _>(&ids)
+                    .execute(conn)?;
+
+                let mut evicted = Vec::new();
+
+                if action == QuotaAction::Evict {
+                    for (name, (quota, incoming)) in quotas {
+                        let oldest = logs::table
+                            .filter(logs::tenant.eq(&tenant))
+                            .filter(logs::name.eq(&name))
+                            .filter(logs::id.ne_all(&ids))
+                            .order((logs::created.desc(), logs::id.desc()))
+                            .offset(quota - incoming)
+                            .select(logs::id)
+                            .load::<Uuid>(conn)?;
+
+                        diesel::delete(logs::table.filter(logs::id.eq_any(&oldest)))
+                            .execute(conn)?;
+                        evicted.push((name, oldest));
+                    }
+                }
+
+                Ok(Inserted {
+                    logs: inserted,
+                    evicted,
+                })
+            })
+        })
+        .await?
+    }
+
+    async fn query(
+        &self,
+        caller: String,
+        query: LogQuery,
+        limit: i64,
+        ascending: bool,
+    ) -> Result<Vec<Log>, QueryError> {
+        let conn = self.conn().await?;
+        let q = build_log_query(caller, query, limit, ascending);
+
+        Ok(interact(conn, self.query_timeout, move |conn| q.load(conn)).await??)
+    }
+
+    async fn count(&self, caller: String, query: LogQuery) -> Result<i64, QueryError> {
+        let conn = self.conn().await?;
+        let q = filter_logs(caller, query);
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            q.count().get_result(conn)
+        })
+        .await??)
+    }
+
+    async fn names(&self, caller: String) -> Result<Vec<String>, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .select(logs::name)
+                .distinct()
+                .order(logs::name.asc())
+                .load(conn)
+        })
+        .await??)
+    }
+
+    async fn scan(
+        &self,
+        caller: String,
+        scan: Scan,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Log>, QueryError> {
+        use self::logs::dsl::{created, id};
+
+        let conn = self.conn().await?;
+        let mut q = filter_logs(caller, scan.query);
+
+        if let Some((after_created, after_id)) = scan.after {
+            q = if scan.ascending {
+                q.filter(
+                    created
+                        .gt(after_created)
+                        .or(created.eq(after_created).and(id.gt(after_id))),
+                )
             } else {
-                logs.limit(limit).select(Log::as_select()).load(conn)
-            }
+                q.filter(
+                    created
+                        .lt(after_created)
+                        .or(created.eq(after_created).and(id.lt(after_id))),
+                )
+            };
+        }
+
+        if let Some((through_created, through_id)) = scan.through {
+            q = if scan.ascending {
+                q.filter(
+                    created
+                        .lt(through_created)
+                        .or(created.eq(through_created).and(id.le(through_id))),
+                )
+            } else {
+                q.filter(
+                    created
+                        .gt(through_created)
+                        .or(created.eq(through_created).and(id.ge(through_id))),
+                )
+            };
+        }
+
+        let q = if scan.ascending {
+            q.order((created.asc(), id.asc()))
+        } else {
+            q.order((created.desc(), id.desc()))
+        }
+        .offset(offset)
+        .limit(limit);
+
+        Ok(interact(conn, self.query_timeout, move |conn| q.load(conn)).await??)
+    }
+
+    async fn latest(&self, caller: String, name: Option<String>) -> Result<Vec<Log>, QueryError> {
+        let conn = self.conn().await?;
+        let mut q = logs::table
+            .select(Log::as_select())
+            .filter(logs::tenant.eq(caller))
+            .filter(logs::deleted_at.is_null())
+            .distinct_on(logs::name)
+            .order((logs::name.asc(), logs::created.desc(), logs::id.desc()))
+            .into_boxed();
+
+        if let Some(name) = name {
+            q = q.filter(logs::name.eq(name));
+        }
+
+        Ok(interact(conn, self.query_timeout, move |conn| q.load(conn)).await??)
+    }
+
+    async fn counts_by_name(
+        &self,
+        caller: String,
+        created_after: Option<chrono::DateTime<chrono::Utc>>,
+        created_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<(String, i64)>, QueryError> {
+        use self::logs::dsl::{created, deleted_at, logs, name, tenant};
+
+        let conn = self.conn().await?;
+        let mut q = logs
+            .filter(tenant.eq(caller))
+            .filter(deleted_at.is_null())
+            .group_by(name)
+            .select((name, count_star()))
+            .into_boxed();
+
+        if let Some(after) = created_after {
+            q = q.filter(created.ge(after));
+        }
+
+        if let Some(before) = created_before {
+            q = q.filter(created.le(before));
+        }
+
+        Ok(interact(conn, self.query_timeout, move |conn| q.load(conn)).await??)
+    }
+
+    async fn timeseries(
+        &self,
+        caller: String,
+        interval: Interval,
+        created_after: chrono::DateTime<chrono::Utc>,
+        created_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(chrono::DateTime<chrono::Utc>, i64)>, QueryError> {
+        let conn = self.conn().await?;
+        let unit = match interval {
+            Interval::Hour => "hour",
+            Interval::Day => "day",
+        };
+
+        let buckets = interact(conn, self.query_timeout, move |conn| {
+            diesel::sql_query(
+                "SELECT date_trunc($1, created) AS bucket, count(*) AS count FROM logs \
+                 WHERE tenant = $4 AND deleted_at IS NULL AND created >= $2 AND created <= $3 GROUP BY bucket ORDER BY bucket",
+            )
+            .bind::<Text, _>(unit)
+            .bind::<Timestamptz, _>(created_after)
+            .bind::<Timestamptz, _>(created_before)
+            .bind::<Text, _>(caller)
+            .load::<Bucket>(conn)
         })
-        .await;
+        .await??;
 
-    if let Err(err) = query {
-        eprintln!("Error: {err}");
-        return Json(
-            json!({ "status": StatusCode::INTERNAL_SERVER_ERROR.as_u16(), "message": "Could not get data" }),
-        );
+        Ok(buckets
+            .into_iter()
+            .map(|bucket| (bucket.bucket, bucket.count))
+            .collect())
     }
 
-    let res = query.unwrap().unwrap();
-    let mut response = Vec::<serde_json::Value>::with_capacity(res.len());
+    async fn delete_older_than(
+        &self,
+        caller: Option<String>,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, QueryError> {
+        use self::logs::dsl::{created, logs, tenant};
 
-    for log in res.into_iter() {
-        response.push(json!({
-            "id": log.id.to_string(),
-            "name": log.name,
-            "data": log.data,
-            "created": log.created,
-        }));
+        let conn = self.conn().await?;
+
+        Ok(
+            interact(conn, self.query_timeout, move |conn| match caller {
+                Some(caller) => {
+                    diesel::delete(logs.filter(created.lt(cutoff)).filter(tenant.eq(caller)))
+                        .execute(conn)
+                }
+                None => diesel::delete(logs.filter(created.lt(cutoff))).execute(conn),
+            })
+            .await??,
+        )
+    }
+
+    async fn find_duplicates(
+        &self,
+        caller: String,
+        hashes: Vec<String>,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Log>, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .filter(logs::hash.eq_any(hashes))
+                .filter(logs::created.ge(since))
+                .order((logs::created.desc(), logs::id.desc()))
+                .select(Log::as_select())
+                .load(conn)
+        })
+        .await??)
+    }
+
+    async fn get(&self, caller: String, id: Uuid) -> Result<Option<Log>, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .find(id)
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .select(Log::as_select())
+                .first(conn)
+                .optional()
+        })
+        .await??)
+    }
+
+    async fn update(
+        &self,
+        caller: String,
+        id: Uuid,
+        name: Option<String>,
+        data: Option<serde_json::Value>,
+    ) -> Result<Option<Log>, QueryError> {
+        let conn = self.conn().await?;
+        let threshold = self.compress_threshold;
+
+        interact_transaction(conn, self.query_timeout, move |conn| {
+            let Some(current) = logs::table
+                .find(id)
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .select(Log::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let patched = current.patched(name, data).stored(threshold);
+
+            diesel::update(logs::table.find(id))
+                .set(LogChanges::from(patched))
+                .returning(Log::as_returning())
+                .get_result(conn)
+                .map(Some)
+        })
+        .await
+    }
+
+    async fn delete(&self, caller: String, id: Uuid, hard: bool) -> Result<usize, QueryError> {
+        let conn = self.conn().await?;
+        let found = logs::table.find(id).filter(logs::tenant.eq(caller));
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            if hard {
+                diesel::delete(found).execute(conn)
+            } else {
+                diesel::update(found.filter(logs::deleted_at.is_null()))
+                    .set(logs::deleted_at.eq(chrono::Utc::now()))
+                    .execute(conn)
+            }
+        })
+        .await??)
+    }
+
+    async fn delete_named(
+        &self,
+        caller: String,
+        name: String,
+        hard: bool,
+    ) -> Result<Vec<Uuid>, QueryError> {
+        let conn = self.conn().await?;
+        let found = logs::table
+            .filter(logs::tenant.eq(caller))
+            .filter(logs::name.eq(name));
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            if hard {
+                diesel::delete(found)
+                    .returning(logs::id)
+                    .get_results::<Uuid>(conn)
+            } else {
+                diesel::update(found.filter(logs::deleted_at.is_null()))
+                    .set(logs::deleted_at.eq(chrono::Utc::now()))
+                    .returning(logs::id)
+                    .get_results::<Uuid>(conn)
+            }
+        })
+        .await??)
+    }
+
+    async fn restore(&self, caller: String, id: Uuid) -> Result<usize, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            diesel::update(
+                logs::table
+                    .find(id)
+                    .filter(logs::tenant.eq(caller))
+                    .filter(logs::deleted_at.is_not_null()),
+            )
+            .set(logs::deleted_at.eq(None::<chrono::DateTime<chrono::Utc>>))
+            .execute(conn)
+        })
+        .await??)
+    }
+
+    async fn record_audit(&self, entry: AuditEntry) -> Result<(), QueryError> {
+        let conn = self.conn().await?;
+
+        interact(conn, self.query_timeout, move |conn| {
+            insert_audit(conn, &entry)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    async fn audit(
+        &self,
+        caller: String,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            audit::table
+                .filter(audit::tenant.eq(caller).or(audit::tenant.is_null()))
+                .order((audit::created.desc(), audit::id.desc()))
+                .offset(offset)
+                .limit(limit)
+                .select(AuditEntry::as_select())
+                .load(conn)
+        })
+        .await??)
+    }
+}
+
+fn insert_audit(conn: &mut PgConnection, entry: &AuditEntry) -> QueryResult<usize> {
+    diesel::insert_into(audit::table)
+        .values(entry)
+        .execute(conn)
+}
+
+async fn ping_database(db: &Pool, timeout: Duration) -> Result<(), String> {
+    let conn = db.get().await.map_err(|err| err.to_string())?;
+
+    interact(conn, timeout, |conn| {
+        diesel::sql_query("SELECT 1").execute(conn)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+fn database_pool(connection: &str, config: &Config) -> Result<Pool, BuildError> {
+    let manager = Manager::from_config(
+        connection,
+        deadpool_diesel::Runtime::Tokio1,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Verified,
+        },
+    );
+
+    Pool::builder(manager)
+        .max_size(config.pool_size)
+        .wait_timeout(Some(POOL_WAIT_TIMEOUT))
+        .runtime(deadpool_diesel::Runtime::Tokio1)
+        .post_create(statement_timeout(config.query_timeout))
+        .build()
+}
+
+fn statement_timeout(timeout: Duration) -> Hook<Manager> {
+    Hook::async_fn(
+        move |conn: &mut deadpool_diesel::Connection<PgConnection>, _| {
+            Box::pin(async move {
+                let set = format!("SET statement_timeout = {}", timeout.as_millis());
+
+                tokio::time::timeout(
+                    timeout,
+                    conn.interact(move |conn| diesel::sql_query(set).execute(conn)),
+                )
+                .await
+                .map_err(|_| HookError::Message("Timed out setting statement_timeout".into()))?
+                .map_err(|err| HookError::Message(err.to_string()))?
+                .map_err(|err| HookError::Message(err.to_string()))?;
+
+                Ok(())
+            })
+        },
+    )
+}
+
+#[derive(Debug)]
+enum QueryError {
+    Unavailable(String),
+    Timeout,
+    Interact(deadpool_diesel::InteractError),
+    Database(diesel::result::Error),
+    OverQuota(String, i64),
+    #[cfg(feature = "sqlite")]
+    Corrupt(String),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Unavailable(err) => write!(f, "{err}"),
+            QueryError::Timeout => write!(f, "Database query timed out"),
+            QueryError::Interact(err) => write!(f, "{err}"),
+            QueryError::Database(err) => write!(f, "{err}"),
+            QueryError::OverQuota(name, quota) => {
+                write!(f, "Quota of {quota} logs for name \"{name}\" reached")
+            }
+            #[cfg(feature = "sqlite")]
+            QueryError::Corrupt(err) => write!(f, "Corrupt row: {err}"),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for QueryError {
+    fn from(err: diesel::result::Error) -> Self {
+        QueryError::Database(err)
+    }
+}
+
+async fn interact<M, C, F, R>(
+    conn: deadpool::managed::Object<M>,
+    timeout: Duration,
+    f: F,
+) -> Result<R, QueryError>
+where
+    M: deadpool::managed::Manager<Type = deadpool_diesel::Connection<C>>,
+    C: Send + 'static,
+    F: FnOnce(&mut C) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let result = tokio::time::timeout(timeout, conn.interact(f)).await;
+
+    match result {
+        Ok(result) => result.map_err(QueryError::Interact),
+        Err(_) => {
+            drop(deadpool::managed::Object::take(conn));
+            Err(QueryError::Timeout)
+        }
+    }
+}
+
+async fn interact_transaction<M, C, F, R>(
+    conn: deadpool::managed::Object<M>,
+    timeout: Duration,
+    f: F,
+) -> Result<R, QueryError>
+where
+    M: deadpool::managed::Manager<Type = deadpool_diesel::Connection<C>>,
+    C: diesel::Connection + Send + 'static,
+    F: FnOnce(&mut C) -> Result<R, diesel::result::Error> + Send + 'static,
+    R: Send + 'static,
+{
+    Ok(interact(conn, timeout, move |conn| conn.transaction(f)).await??)
+}
+
+impl QueryError {
+    fn is_connection(&self) -> bool {
+        match self {
+            QueryError::Unavailable(_) => true,
+            QueryError::Database(diesel::result::Error::DatabaseError(kind, _)) => matches!(
+                kind,
+                DatabaseErrorKind::ClosedConnection | DatabaseErrorKind::UnableToSendCommand
+            ),
+            _ => false,
+        }
+    }
+}
+
+fn query_error(err: QueryError, message: &'static str) -> ApiError {
+    if let QueryError::OverQuota(..) = err {
+        return ApiError::QuotaExceeded(err.to_string());
+    }
+
+    error!("{err}");
+
+    if err.is_connection() {
+        return ApiError::DbUnavailable;
+    }
+
+    match err {
+        QueryError::Timeout => ApiError::DbTimeout,
+        _ => ApiError::Internal(message),
+    }
+}
+
+enum ApiError {
+    BadRequest(String),
+    InvalidJson(String),
+    InvalidBody(String),
+    Unauthorized(&'static str),
+    NotFound,
+    Conflict(&'static str),
+    PayloadTooLarge(String),
+    SchemaMismatch(Vec<serde_json::Value>),
+    RateLimited(Duration),
+    QuotaExceeded(String),
+    ReadOnly,
+    DbUnavailable,
+    DbTimeout,
+    Internal(&'static str),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, code, message) = match &self {
+            ApiError::BadRequest(message) => {
+                (StatusCode::BAD_REQUEST, "bad_request", message.as_str())
+            }
+            ApiError::InvalidJson(message) => {
+                (StatusCode::BAD_REQUEST, "invalid_json", message.as_str())
+            }
+            ApiError::InvalidBody(message) => {
+                (StatusCode::BAD_REQUEST, "invalid_body", message.as_str())
+            }
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, "unauthorized", *message),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not_found", "Not found"),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, "conflict", *message),
+            ApiError::PayloadTooLarge(message) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                message.as_str(),
+            ),
+            ApiError::SchemaMismatch(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "schema_mismatch",
+                "Data does not match schema",
+            ),
+            ApiError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+                "Too many requests",
+            ),
+            ApiError::QuotaExceeded(message) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "quota_exceeded",
+                message.as_str(),
+            ),
+            ApiError::ReadOnly => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "read_only",
+                "Server is in read-only mode",
+            ),
+            ApiError::DbUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "db_unavailable",
+                "Database unavailable",
+            ),
+            ApiError::DbTimeout => (
+                StatusCode::GATEWAY_TIMEOUT,
+                "db_timeout",
+                "Database query timed out",
+            ),
+            ApiError::Internal(message) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal", *message)
+            }
+        };
+        let mut body = json!({ "status": status.as_u16(), "code": code, "message": message });
+
+        if let ApiError::SchemaMismatch(errors) = &self {
+            body["errors"] = json!(errors);
+        }
+
+        let mut response = (status, Json(body)).into_response();
+
+        if let ApiError::RateLimited(retry_after) = self {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+        }
+
+        if let ApiError::DbUnavailable = self {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from_static(UNAVAILABLE_RETRY_AFTER),
+            );
+        }
+
+        response
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(err: QueryRejection) -> Self {
+        ApiError::BadRequest(err.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(err: JsonRejection) -> Self {
+        match err {
+            JsonRejection::JsonSyntaxError(_) | JsonRejection::MissingJsonContentType(_) => {
+                ApiError::InvalidJson(err.body_text())
+            }
+            JsonRejection::JsonDataError(_) => ApiError::InvalidBody(err.body_text()),
+            _ if err.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                ApiError::PayloadTooLarge(err.body_text())
+            }
+            _ => ApiError::BadRequest(err.body_text()),
+        }
+    }
+}
+
+impl From<StringRejection> for ApiError {
+    fn from(err: StringRejection) -> Self {
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge(err.body_text())
+        } else {
+            ApiError::BadRequest(err.body_text())
+        }
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(err: PathRejection) -> Self {
+        ApiError::BadRequest(err.body_text())
+    }
+}
+
+async fn wait_for_database(db: &Pool, retries: u32, timeout: Duration) -> Result<(), String> {
+    let mut attempt = 1;
+
+    loop {
+        match ping_database(db, timeout).await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < retries => {
+                let delay = backoff_delay(attempt);
+                warn!("Database not ready (attempt {attempt}/{retries}): {err}, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+    CONNECT_BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(CONNECT_BACKOFF_MAX)
+}
+
+async fn run_migrations(db: &Pool, timeout: Duration) -> Result<usize, String> {
+    let conn = db.get().await.map_err(|err| err.to_string())?;
+
+    interact(conn, timeout, |conn| {
+        conn.run_pending_migrations(MIGRATIONS)
+            .map(|applied| applied.len())
+            .map_err(|err| err.to_string())
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+async fn check_schema(db: &Pool, timeout: Duration) -> Result<(), String> {
+    let conn = db.get().await.map_err(|err| err.to_string())?;
+
+    interact(conn, timeout, |conn| {
+        logs::table
+            .select(Log::as_select())
+            .limit(1)
+            .load(conn)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+fn init_tracing(settings: &Settings) {
+    let (filter, invalid) = log_filter(settings.var(EnvFilter::DEFAULT_ENV).ok().as_deref());
+
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    if let Some(err) = invalid {
+        warn!("{err}, falling back to {DEFAULT_LOG_FILTER}");
+    }
+}
+
+fn log_filter(value: Option<&str>) -> (EnvFilter, Option<String>) {
+    match value {
+        Some(value) => match EnvFilter::try_new(value) {
+            Ok(filter) => (filter, None),
+            Err(err) => (
+                EnvFilter::new(DEFAULT_LOG_FILTER),
+                Some(format!("Invalid RUST_LOG value \"{value}\": {err}")),
+            ),
+        },
+        None => (EnvFilter::new(DEFAULT_LOG_FILTER), None),
+    }
+}
+
+#[derive(Clone)]
+struct Tenant(String);
+
+#[derive(Clone)]
+struct KeyHash(String);
+
+#[derive(Clone)]
+struct Admin;
+
+async fn auth(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<KeyRequest>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let key = presented_key(
+        request.headers().get(header::AUTHORIZATION),
+        query.key.as_deref(),
+    );
+    let config = state.config();
+    let Some(tenant) = key_tenant(&config, key) else {
+        state.metrics.auth_failures.inc();
+
+        return ApiError::Unauthorized("Unauthorized").into_response();
+    };
+    let key_hash = key.map(|key| hex::encode(Sha256::digest(key)));
+    let admin = key.is_some_and(|key| config.admin_keys.contains(key));
+
+    request.extensions_mut().insert(Tenant(tenant.to_string()));
+
+    if admin {
+        request.extensions_mut().insert(Admin);
+    }
+
+    if let Some(key_hash) = key_hash {
+        request.extensions_mut().insert(KeyHash(key_hash));
+    }
+
+    next.run(request).await
+}
+
+async fn identify(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<KeyRequest>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.headers().contains_key(header::AUTHORIZATION) || query.key.is_some() {
+        return auth(State(state), Query(query), request, next).await;
+    }
+
+    request
+        .extensions_mut()
+        .insert(Tenant(DEFAULT_TENANT.to_string()));
+
+    next.run(request).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrettyRequest {
+    pretty: Option<bool>,
+}
+
+async fn pretty_json(request: Request, next: Next) -> Response {
+    let pretty = Query::<PrettyRequest>::try_from_uri(request.uri())
+        .is_ok_and(|Query(query)| query.pretty.unwrap_or(false));
+
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+
+    if !pretty || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+
+    let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+        return ApiError::Internal("Could not read response").into_response();
+    };
+
+    let body = match serde_json::from_slice::<serde_json::Value>(&body)
+        .and_then(|value| serde_json::to_vec_pretty(&value))
+    {
+        Ok(pretty) => pretty,
+        Err(_) => body.to_vec(),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn deprecated(request: Request, next: Next) -> Response {
+    let successor = format!(
+        "<{API_PREFIX}{}>; rel=\"successor-version\"",
+        request.uri().path()
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    headers.insert(
+        HeaderName::from_static(DEPRECATION_HEADER),
+        HeaderValue::from_static("true"),
+    );
+
+    if let Ok(successor) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, successor);
+    }
+
+    response
+}
+
+async fn preflight_no_content(request: Request, next: Next) -> Response {
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = next.run(request).await;
+
+    if preflight
+        && response.status() == StatusCode::OK
+        && response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+    }
+
+    response
+}
+
+async fn shutdown_signal(state: Arc<AppState>) {
+    let mut stopping = state.shutdown.subscribe();
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = stopping.wait_for(|stopping| *stopping) => {},
+    }
+
+    info!("Shutting down gracefully");
+
+    state.shutdown.send_replace(true);
+}
+
+async fn trace_request(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LENGTH)
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id = %request_id,
+    );
+
+    async move {
+        let start = Instant::now();
+        let mut response = next.run(request).await;
+
+        if let Ok(request_id) = HeaderValue::from_str(&request_id) {
+            response
+                .headers_mut()
+                .insert(HeaderName::from_static(REQUEST_ID_HEADER), request_id);
+        }
+
+        state
+            .metrics
+            .request_duration
+            .observe(start.elapsed().as_secs_f64());
+
+        let status = response.status().as_u16();
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        if response.status().is_server_error() {
+            warn!(status, latency_ms, "finished request");
+        } else {
+            info!(status, latency_ms, "finished request");
+        }
+
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+async fn reject_writes(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.read_only.load(Ordering::Relaxed) {
+        return ApiError::ReadOnly.into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn require_admin(request: Request, next: Next) -> Response {
+    if request.extensions().get::<Admin>().is_none() {
+        return ApiError::Unauthorized("Admin endpoints require a KEY or KEYS key").into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(retry_after) = check_rate_limit(&state, addr.ip()) {
+        return ApiError::RateLimited(retry_after).into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn verify_signature(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = state.config();
+    let Some(secret) = &config.hmac_secret else {
+        return next.run(request).await;
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, config.max_batch_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => return ApiError::PayloadTooLarge("Payload too large".into()).into_response(),
+    };
+
+    if let Err(message) = check_signature(secret, config.signature_max_age, &parts.headers, &body) {
+        state.metrics.auth_failures.inc();
+
+        return ApiError::Unauthorized(message).into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+fn check_signature(
+    secret: &[u8],
+    max_age: Duration,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), &'static str> {
+    let timestamp = headers
+        .get(TIMESTAMP_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("Missing X-Timestamp")?;
+    let signed_at = timestamp
+        .parse::<i64>()
+        .map_err(|_| "Invalid X-Timestamp")?;
+
+    if chrono::Utc::now().timestamp().abs_diff(signed_at) > max_age.as_secs() {
+        return Err("Stale X-Timestamp");
+    }
+
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| hex::decode(value).ok())
+        .ok_or("Missing X-Signature")?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|_| "Invalid X-Signature")?;
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.verify_slice(&signature)
+        .map_err(|_| "Invalid X-Signature")
+}
+
+fn check_rate_limit(state: &AppState, ip: IpAddr) -> Result<(), Duration> {
+    let max = state.config().rate_limit_per_min;
+
+    if max == 0 {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let mut rate_limits = state.rate_limits.lock().unwrap();
+
+    if rate_limits.len() > RATE_LIMIT_MAX_TRACKED {
+        rate_limits.retain(|_, hits| {
+            hits.back()
+                .is_some_and(|hit| now.duration_since(*hit) < RATE_LIMIT_WINDOW)
+        });
+    }
+
+    let hits = rate_limits.entry(ip).or_default();
+
+    while hits
+        .front()
+        .is_some_and(|hit| now.duration_since(*hit) >= RATE_LIMIT_WINDOW)
+    {
+        hits.pop_front();
+    }
+
+    if hits.len() >= max {
+        return Err(RATE_LIMIT_WINDOW - now.duration_since(hits[0]));
+    }
+
+    hits.push_back(now);
+
+    Ok(())
+}
+
+fn presented_key<'a>(header: Option<&'a HeaderValue>, query: Option<&'a str>) -> Option<&'a str> {
+    header
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(query)
+}
+
+fn key_tenant<'a>(config: &'a Config, key: Option<&str>) -> Option<&'a str> {
+    key.and_then(|key| config.keys.get(key)).map(String::as_str)
+}
+
+fn bind_address(host: &str, port: u16) -> Result<String, String> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port).to_string());
+    }
+
+    let valid_hostname = !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    if valid_hostname {
+        Ok(format!("{host}:{port}"))
+    } else {
+        Err(format!(
+            "Invalid HOST value \"{host}\", expected an IP address or hostname"
+        ))
+    }
+}
+
+fn parse_cors_origins(value: &str) -> Result<AllowOrigin, String> {
+    if value.trim() == "*" {
+        return Ok(AllowOrigin::any());
+    }
+
+    let origins = value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .map_err(|_| format!("Invalid CORS_ORIGINS origin \"{origin}\""))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if origins.is_empty() {
+        return Err(format!(
+            "Invalid CORS_ORIGINS value \"{value}\", expected * or comma-separated origins"
+        ));
+    }
+
+    Ok(AllowOrigin::list(origins))
+}
+
+fn load_schemas(dir: &str) -> Result<HashMap<String, JSONSchema>, String> {
+    let mut schemas = HashMap::new();
+
+    for entry in std::fs::read_dir(dir).map_err(|err| err.to_string())? {
+        let path = entry.map_err(|err| err.to_string())?.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let schema = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|schema| {
+                serde_json::from_str::<serde_json::Value>(&schema).map_err(|err| err.to_string())
+            })
+            .and_then(|schema| JSONSchema::compile(&schema).map_err(|err| err.to_string()))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+
+        schemas.insert(name.to_string(), schema);
+    }
+
+    Ok(schemas)
+}
+
+fn schema_errors(config: &Config, req_logs: &[StrippedLog]) -> Vec<serde_json::Value> {
+    req_logs
+        .iter()
+        .enumerate()
+        .flat_map(|(index, req_log)| schema_failures(config, index, &req_log.name, &req_log.data.0))
+        .collect()
+}
+
+fn schema_failures(
+    config: &Config,
+    index: usize,
+    name: &str,
+    data: &serde_json::Value,
+) -> Vec<serde_json::Value> {
+    let Some(schema) = config.schemas.get(name) else {
+        return Vec::new();
+    };
+
+    match schema.validate(data) {
+        Ok(()) => Vec::new(),
+        Err(failures) => failures
+            .map(|failure| {
+                json!({
+                    "index": index,
+                    "path": failure.instance_path.to_string(),
+                    "message": failure.to_string(),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn parse_keys(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| (key.to_string(), DEFAULT_TENANT.to_string()))
+        .collect()
+}
+
+fn parse_tenant_keys(value: &str) -> Result<HashMap<String, String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((DEFAULT_TENANT, _)) => Err(format!(
+                "Invalid TENANT_KEYS entry \"{entry}\", tenant \"{DEFAULT_TENANT}\" is reserved for KEY and KEYS"
+            )),
+            Some((tenant, key)) if !tenant.is_empty() && !key.is_empty() => {
+                Ok((key.to_string(), tenant.to_string()))
+            }
+            _ => Err(format!(
+                "Invalid TENANT_KEYS entry \"{entry}\", expected tenant:key"
+            )),
+        })
+        .collect()
+}
+
+fn parse_quotas(value: &str) -> Result<HashMap<String, i64>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.rsplit_once(':') {
+            Some((name, quota)) if !name.is_empty() => match quota.parse::<i64>() {
+                Ok(quota) if quota >= 1 => Ok((name.to_string(), quota)),
+                _ => Err(format!(
+                    "Invalid QUOTAS entry \"{entry}\", expected name:count with count >= 1"
+                )),
+            },
+            _ => Err(format!(
+                "Invalid QUOTAS entry \"{entry}\", expected name:count"
+            )),
+        })
+        .collect()
+}
+
+fn parse_webhook_url(value: &str) -> Result<reqwest::Url, String> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(url),
+        _ => Err(format!(
+            "Invalid WEBHOOK_URL value \"{value}\", expected an http:// or https:// URL"
+        )),
+    }
+}
+
+fn parse_read_only(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!(
+            "Invalid READ_ONLY value \"{value}\", expected true or false"
+        )),
+    }
+}
+
+fn parse_quota_action(value: &str) -> Result<QuotaAction, String> {
+    match value {
+        "reject" => Ok(QuotaAction::Reject),
+        "evict" => Ok(QuotaAction::Evict),
+        _ => Err(format!(
+            "Invalid QUOTA_ACTION value \"{value}\", expected reject or evict"
+        )),
+    }
+}
+
+fn parse_pool_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if size >= 1 => Ok(size),
+        _ => Err(format!(
+            "Invalid DB_POOL_SIZE value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_connect_retries(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(retries) if retries >= 1 => Ok(retries),
+        _ => Err(format!(
+            "Invalid DB_CONNECT_RETRIES value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_query_timeout(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(millis) if millis >= 1 => Ok(Duration::from_millis(millis)),
+        _ => Err(format!(
+            "Invalid DB_QUERY_TIMEOUT_MS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_retention_days(value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(days) if retention_cutoff(days).is_some() => Ok(days),
+        _ => Err(format!(
+            "Invalid RETENTION_DAYS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_retention_sweep(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs >= 1 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!(
+            "Invalid RETENTION_SWEEP_SECS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_dedupe_window(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs >= 1 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!(
+            "Invalid DEDUPE_WINDOW_SECS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_signature_max_age(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs >= 1 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!(
+            "Invalid HMAC_MAX_AGE_SECS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_max_all_rows(value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(rows) if rows >= 1 => Ok(rows),
+        _ => Err(format!(
+            "Invalid MAX_ALL_ROWS value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_compress_threshold(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(bytes) if bytes >= 1 => Ok(bytes),
+        _ => Err(format!(
+            "Invalid COMPRESS_THRESHOLD_BYTES value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    value
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid PORT value \"{value}\", expected integer 0-65535"))
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid {name} value \"{value}\", expected integer >= 0"))
+}
+
+fn parse_size(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if size >= 1 => Ok(size),
+        _ => Err(format!(
+            "Invalid {name} value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn parse_limit(value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(limit) if limit >= 1 => Ok(limit),
+        _ => Err(format!(
+            "Invalid LIMIT value \"{value}\", expected integer >= 1"
+        )),
+    }
+}
+
+fn valid_page(offset: Option<i64>, limit: Option<i64>) -> bool {
+    offset.is_none_or(|o| o >= 0) && limit.is_none_or(|l| l >= 1)
+}
+
+#[derive(Clone, Identifiable, Insertable, Selectable, Eq, PartialEq, Serialize, ToSchema)]
+#[diesel(table_name = logs)]
+struct Log {
+    id: Uuid,
+    name: String,
+    data: serde_json::Value,
+    created: chrono::DateTime<chrono::Utc>,
+    level: String,
+    updated: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip)]
+    tenant: String,
+    #[serde(skip)]
+    hash: Option<String>,
+    #[serde(skip)]
+    data_compressed: Option<Vec<u8>>,
+}
+
+type LogRow = (
+    Uuid,
+    String,
+    serde_json::Value,
+    chrono::DateTime<chrono::Utc>,
+    String,
+    Option<chrono::DateTime<chrono::Utc>>,
+    String,
+    Option<String>,
+    Option<Vec<u8>>,
+);
+
+impl<ST, DB> Queryable<ST, DB> for Log
+where
+    DB: diesel::backend::Backend,
+    LogRow: diesel::deserialize::FromStaticSqlRow<ST, DB>,
+{
+    type Row = LogRow;
+
+    fn build(
+        (id, name, data, created, level, updated, tenant, hash, data_compressed): Self::Row,
+    ) -> diesel::deserialize::Result<Self> {
+        let data = match data_compressed {
+            Some(bytes) => decompress_data(&bytes)?,
+            None => data,
+        };
+
+        Ok(Log {
+            id,
+            name,
+            data,
+            created,
+            level,
+            updated,
+            tenant,
+            hash,
+            data_compressed: None,
+        })
+    }
+}
+
+fn stored_data(
+    data: serde_json::Value,
+    threshold: Option<usize>,
+) -> (serde_json::Value, Option<Vec<u8>>) {
+    let serialized = data.to_string();
+
+    match threshold {
+        Some(threshold) if serialized.len() > threshold => {
+            (serde_json::Value::Null, Some(compress_data(&serialized)))
+        }
+        _ => (data, None),
+    }
+}
+
+fn compress_data(serialized: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    encoder
+        .write_all(serialized.as_bytes())
+        .and_then(|_| encoder.finish())
+        .expect("writing to a Vec cannot fail")
+}
+
+fn decompress_data(bytes: &[u8]) -> Result<serde_json::Value, String> {
+    serde_json::from_reader(GzDecoder::new(bytes)).map_err(|err| err.to_string())
+}
+
+#[derive(Insertable, Queryable, Selectable, Serialize, ToSchema)]
+#[diesel(table_name = audit)]
+struct AuditEntry {
+    id: Uuid,
+    operation: String,
+    #[serde(skip)]
+    tenant: Option<String>,
+    key_hash: Option<String>,
+    affected: i64,
+    ids: serde_json::Value,
+    created: chrono::DateTime<chrono::Utc>,
+}
+
+impl AuditEntry {
+    fn new(
+        operation: &str,
+        tenant: Option<String>,
+        key_hash: Option<String>,
+        affected: usize,
+        ids: Vec<Uuid>,
+    ) -> Self {
+        AuditEntry {
+            id: Uuid::new_v4(),
+            operation: operation.to_string(),
+            tenant,
+            key_hash,
+            affected: affected as i64,
+            ids: json!(ids.into_iter().take(AUDIT_MAX_IDS).collect::<Vec<_>>()),
+            created: chrono::Utc::now(),
+        }
+    }
+}
+
+async fn record_audit(state: &AppState, entry: AuditEntry) {
+    if entry.affected == 0 {
+        return;
+    }
+
+    if let Err(err) = state.store.record_audit(entry).await {
+        error!("Could not record audit entry: {err}");
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TimeFormat {
+    Rfc3339,
+    EpochMs,
+}
+
+impl Log {
+    fn patched(self, name: Option<String>, data: Option<serde_json::Value>) -> Log {
+        let name = name.unwrap_or(self.name);
+        let data = data.unwrap_or(self.data);
+
+        Log {
+            hash: Some(content_hash(&name, &data)),
+            updated: Some(chrono::Utc::now()),
+            name,
+            data,
+            ..self
+        }
+    }
+
+    fn stored(&self, threshold: Option<usize>) -> Log {
+        let (data, data_compressed) = stored_data(self.data.clone(), threshold);
+
+        Log {
+            data,
+            data_compressed,
+            ..self.clone()
+        }
+    }
+
+    fn into_json(self) -> serde_json::Value {
+        self.into_json_with(TimeFormat::Rfc3339)
+    }
+
+    fn into_json_with(self, times: TimeFormat) -> serde_json::Value {
+        let (created, updated) = match times {
+            TimeFormat::Rfc3339 => (json!(self.created), json!(self.updated)),
+            TimeFormat::EpochMs => (
+                json!(self.created.timestamp_millis()),
+                json!(self.updated.map(|updated| updated.timestamp_millis())),
+            ),
+        };
+
+        json!({
+            "id": self.id.to_string(),
+            "name": self.name,
+            "data": self.data,
+            "created": created,
+            "level": self.level,
+            "updated": updated,
+        })
+    }
+}
+
+fn project(log: serde_json::Value, fields: Option<&[&str]>) -> serde_json::Value {
+    match (log, fields) {
+        (serde_json::Value::Object(mut log), Some(fields)) => {
+            log.retain(|key, _| fields.contains(&key.as_str()));
+            serde_json::Value::Object(log)
+        }
+        (log, _) => log,
+    }
+}
+
+struct LogData(serde_json::Value);
+
+impl<'de> Deserialize<'de> for LogData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let data = serde_json::Value::deserialize(deserializer)?;
+
+        if exceeds_depth(&data, MAX_DATA_DEPTH) {
+            return Err(serde::de::Error::custom(format!(
+                "data is nested deeper than {MAX_DATA_DEPTH} levels"
+            )));
+        }
+
+        let size = serde_json::to_vec(&data)
+            .map_err(serde::de::Error::custom)?
+            .len();
+
+        if size > MAX_DATA_BYTES {
+            return Err(serde::de::Error::custom(format!(
+                "data is {size} bytes, larger than {MAX_DATA_BYTES} bytes"
+            )));
+        }
+
+        Ok(LogData(data))
+    }
+}
+
+fn exceeds_depth(value: &serde_json::Value, depth: usize) -> bool {
+    match value {
+        serde_json::Value::Array(values) => {
+            depth == 0 || values.iter().any(|value| exceeds_depth(value, depth - 1))
+        }
+        serde_json::Value::Object(map) => {
+            depth == 0 || map.values().any(|value| exceeds_depth(value, depth - 1))
+        }
+        _ => false,
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct StrippedLog {
+    name: String,
+    #[schema(value_type = Value)]
+    data: LogData,
+    level: Option<String>,
+    created: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+enum LogBody {
+    Batch(Vec<StrippedLog>),
+    Single(StrippedLog),
+}
+
+impl<'de> Deserialize<'de> for LogBody {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct LogBodyVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for LogBodyVisitor {
+            type Value = LogBody;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "a log object or an array of logs")
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<LogBody, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                Vec::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))
+                    .map(LogBody::Batch)
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<LogBody, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                StrippedLog::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                    .map(LogBody::Single)
+            }
+        }
+
+        deserializer.deserialize_any(LogBodyVisitor)
+    }
+}
+
+impl<'s> ToSchema<'s> for LogBody {
+    fn schema() -> (
+        &'s str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        let log = utoipa::openapi::Ref::from_schema_name("StrippedLog");
+
+        (
+            "LogBody",
+            utoipa::openapi::OneOfBuilder::new()
+                .item(utoipa::openapi::ArrayBuilder::new().items(log.clone()))
+                .item(log)
+                .into(),
+        )
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "logger-rs"),
+    servers((url = "/v1")),
+    paths(
+        log,
+        log_text,
+        giveme,
+        query_logs,
+        search,
+        stream,
+        export_csv,
+        export,
+        get_log,
+        get_log_data,
+        delete_log,
+        restore_log,
+        patch_log,
+        delete_logs,
+        count,
+        names,
+        latest,
+        stats_by_name,
+        stats_timeseries,
+        retention,
+        audit_log,
+        health_detail,
+        set_read_only,
+        reload_config,
+    ),
+    components(schemas(Log, LogBody, StrippedLog, LogQuery, LogPatch, AuditEntry, ReadOnlyRequest)),
+    modifiers(&ApiDocExtras),
+)]
+struct ApiDoc;
+
+struct ApiDocExtras;
+
+impl Modify for ApiDocExtras {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "key",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+
+        if let Some(mut batch) = openapi.paths.paths.get("/log").cloned() {
+            for operation in batch.operations.values_mut() {
+                operation.operation_id = Some("log_batch".to_string());
+            }
+
+            openapi.paths.paths.insert("/log/batch".to_string(), batch);
+        }
+    }
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn index(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.start_time.elapsed().as_secs()
+    }))
+}
+
+async fn not_found() -> ApiError {
+    ApiError::NotFound
+}
+
+async fn health(State(state): State<Arc<AppState>>) -> (StatusCode, Json<serde_json::Value>) {
+    if check_database(&state).await {
+        (StatusCode::OK, Json(json!({ "status": "ok" })))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "degraded" })),
+        )
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/detail",
+    tag = "health",
+    responses(
+        (status = 200, description = "Database and pool status", body = Object, example = json!({ "status": "ok", "pool": { "size": 1, "available": 1, "max_size": 16 } })),
+        (status = 503, description = "Database unreachable"),
+    ),
+    security(("key" = [])),
+)]
+async fn health_detail(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let healthy = check_database(&state).await;
+    let pool = state.store.status();
+    let pool = json!({
+        "size": pool.size,
+        "available": pool.available,
+        "max_size": pool.max_size,
+    });
+
+    if healthy {
+        (
+            StatusCode::OK,
+            Json(json!({ "status": "ok", "pool": pool })),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "degraded", "pool": pool })),
+        )
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReadOnlyRequest {
+    read_only: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/readonly",
+    tag = "admin",
+    request_body = ReadOnlyRequest,
+    responses(
+        (status = 200, description = "Read-only mode updated", body = Object, example = json!({ "status": 200, "message": "OK", "read_only": true })),
+        (status = 401, description = "Not an admin key"),
+    ),
+    security(("key" = [])),
+)]
+async fn set_read_only(
+    State(state): State<Arc<AppState>>,
+    body: Result<Json<ReadOnlyRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Json(body) = body?;
+
+    state.read_only.store(body.read_only, Ordering::Relaxed);
+
+    if body.read_only {
+        warn!("Read-only mode enabled, writes are rejected");
+    } else {
+        info!("Read-only mode disabled");
+    }
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "read_only": body.read_only }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Configuration reloaded", body = Object, example = json!({ "status": 200, "message": "OK", "restart_required": ["DB_POOL_SIZE"] })),
+        (status = 400, description = "Invalid configuration, nothing was changed"),
+        (status = 401, description = "Not an admin key"),
+    ),
+    security(("key" = [])),
+)]
+async fn reload_config(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let settings = Settings::load().map_err(ApiError::BadRequest)?;
+    let config =
+        Config::from_env(&settings).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    let restart_required = RESTART_SETTINGS
+        .into_iter()
+        .filter(|name| settings.var(name).ok() != state.settings.var(name).ok())
+        .collect::<Vec<_>>();
+
+    let mut current = state.config.write().unwrap();
+    *current = Arc::new(Config {
+        addr: current.addr.clone(),
+        read_only: current.read_only,
+        compress_threshold: current.compress_threshold,
+        max_body_bytes: current.max_body_bytes,
+        max_batch_body_bytes: current.max_batch_body_bytes,
+        pool_size: current.pool_size,
+        connect_retries: current.connect_retries,
+        query_timeout: current.query_timeout,
+        retention_days: current.retention_days,
+        retention_sweep: current.retention_sweep,
+        webhook_url: current.webhook_url.clone(),
+        webhook_secret: current.webhook_secret.clone(),
+        cors_origins: current.cors_origins.clone(),
+        ..config
+    });
+    drop(current);
+
+    info!("Reloaded configuration: {}", settings.effective());
+
+    if !restart_required.is_empty() {
+        warn!(
+            "Restart to apply changed settings: {}",
+            restart_required.join(", ")
+        );
+    }
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "restart_required": restart_required }),
+    ))
+}
+
+async fn check_database(state: &AppState) -> bool {
+    let check = tokio::time::timeout(HEALTH_TIMEOUT, state.store.ping()).await;
+
+    match check {
+        Ok(Ok(_)) => true,
+        Ok(Err(err)) => {
+            error!("{err}");
+            false
+        }
+        Err(_) => {
+            error!("Health check timed out");
+            false
+        }
+    }
+}
+
+async fn metrics(State(state): State<Arc<AppState>>) -> Result<Response, ApiError> {
+    let mut buffer = Vec::new();
+
+    TextEncoder::new()
+        .encode(&state.metrics.registry.gather(), &mut buffer)
+        .map_err(|err| {
+            error!("{err}");
+            ApiError::Internal("Could not encode metrics")
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        buffer,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogOptions {
+    #[serde(rename = "return")]
+    returning: Option<String>,
+    dedupe: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/log",
+    tag = "logs",
+    params(LogOptions),
+    request_body = LogBody,
+    responses(
+        (status = 200, description = "Logs stored", body = Object, example = json!({ "status": 200, "message": "OK", "id": "6f1c6a3e-3f7b-4f43-9c53-3c1f1a9d8e2a" })),
+        (status = 400, description = "Invalid log"),
+        (status = 422, description = "Data does not match schema"),
+    ),
+    security((), ("key" = [])),
+)]
+async fn log(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    options: Result<Query<LogOptions>, QueryRejection>,
+    headers: HeaderMap,
+    req_body: Result<Json<LogBody>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(options) = options?;
+    let Json(req_body) = req_body?;
+
+    store_logs(&state, caller, options, &headers, req_body).await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TextLogOptions {
+    name: Option<String>,
+    level: Option<String>,
+    #[serde(rename = "return")]
+    returning: Option<String>,
+    dedupe: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/log/text",
+    tag = "logs",
+    params(TextLogOptions),
+    request_body(content = String, content_type = "text/plain", description = "Stored as data.message"),
+    responses(
+        (status = 200, description = "Log stored", body = Object, example = json!({ "status": 200, "message": "OK", "id": "6f1c6a3e-3f7b-4f43-9c53-3c1f1a9d8e2a" })),
+        (status = 400, description = "Invalid log"),
+        (status = 413, description = "Body too large"),
+    ),
+    security((), ("key" = [])),
+)]
+async fn log_text(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    options: Result<Query<TextLogOptions>, QueryRejection>,
+    headers: HeaderMap,
+    req_body: Result<String, StringRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(options) = options?;
+    let req_body = req_body?;
+    let message = req_body.trim_end_matches(['\r', '\n']);
+
+    if message.is_empty() {
+        return Err(ApiError::BadRequest("Expected a text body".into()));
+    }
+
+    let req_log = StrippedLog {
+        name: options.name.unwrap_or(DEFAULT_TEXT_NAME.to_string()),
+        data: LogData(json!({ "message": message })),
+        level: options.level,
+        created: None,
+    };
+    let options = LogOptions {
+        returning: options.returning,
+        dedupe: options.dedupe,
+    };
+
+    store_logs(&state, caller, options, &headers, LogBody::Single(req_log)).await
+}
+
+async fn store_logs(
+    state: &AppState,
+    caller: String,
+    options: LogOptions,
+    headers: &HeaderMap,
+    req_body: LogBody,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let config = state.config();
+    let representation = match options.returning.as_deref() {
+        Some("representation") => true,
+        Some("minimal") => false,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Invalid return, expected minimal or representation".into(),
+            ));
+        }
+        None => prefers_representation(headers),
+    };
+
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(|key| format!("{caller}:{key}"));
+
+    let mut reservation = None;
+
+    if let Some(key) = idempotency_key {
+        match reserve_idempotent(state, &key)? {
+            Some(response) => return Ok(Json(response)),
+            None => reservation = Some(IdempotencyReservation { state, key }),
+        }
+    }
+
+    let (req_logs, batch) = match req_body {
+        LogBody::Single(req_log) => (vec![req_log], false),
+        LogBody::Batch(req_logs) => (req_logs, true),
+    };
+
+    if req_logs.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "Batch exceeds {MAX_BATCH_SIZE} logs"
+        )));
+    }
+
+    if req_logs
+        .iter()
+        .any(|req_log| !valid_name(&config, &req_log.name))
+    {
+        return Err(ApiError::BadRequest("Invalid name".into()));
+    }
+
+    if req_logs.iter().any(|req_log| req_log.data.0.is_null()) {
+        return Err(ApiError::BadRequest("Invalid data".into()));
+    }
+
+    if req_logs
+        .iter()
+        .any(|req_log| !valid_level(req_log.level.as_deref()))
+    {
+        return Err(ApiError::BadRequest(invalid_level_message()));
+    }
+
+    let now = chrono::Utc::now();
+
+    if req_logs.iter().any(|req_log| {
+        req_log.created.is_some_and(|created| {
+            (created - now).num_seconds() > MAX_CREATED_SKEW.as_secs() as i64
+        })
+    }) {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid created, more than {}s in the future",
+            MAX_CREATED_SKEW.as_secs()
+        )));
+    }
+
+    let errors = schema_errors(&config, &req_logs);
+
+    if !errors.is_empty() {
+        return Err(ApiError::SchemaMismatch(errors));
+    }
+
+    if req_logs.is_empty() {
+        return Ok(Json(
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "inserted": 0 }),
+        ));
+    }
+
+    let rows = req_logs
+        .into_iter()
+        .map(|req_body| Log {
+            hash: Some(content_hash(&req_body.name, &req_body.data.0)),
+            id: Uuid::new_v4(),
+            name: req_body.name,
+            data: req_body.data.0,
+            created: req_body.created.unwrap_or(now),
+            level: req_body.level.unwrap_or(DEFAULT_LEVEL.to_string()),
+            updated: None,
+            tenant: caller.clone(),
+            data_compressed: None,
+        })
+        .collect::<Vec<_>>();
+    let dedupe = options.dedupe.unwrap_or(false);
+    let mut seen = HashMap::new();
+
+    if dedupe {
+        let hashes = rows.iter().filter_map(|row| row.hash.clone()).collect();
+        let since = now - chrono::Duration::seconds(config.dedupe_window.as_secs() as i64);
+
+        for log in state
+            .store
+            .find_duplicates(caller.clone(), hashes, since)
+            .await
+            .map_err(|err| query_error(err, "Could not log data"))?
+        {
+            if let Some(hash) = log.hash.clone() {
+                seen.entry(hash).or_insert(log);
+            }
+        }
+    }
+
+    let mut fresh = Vec::with_capacity(rows.len());
+    let mut slots = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        if let Some(hash) = row.hash.clone().filter(|_| dedupe) {
+            if let Some(existing) = seen.get(&hash) {
+                slots.push(Some(existing.clone()));
+                continue;
+            }
+
+            seen.insert(hash, row.clone());
+        }
+
+        slots.push(None);
+        fresh.push(row);
+    }
+
+    let quotas = quota_usage(&config, &fresh);
+
+    if let Some((name, (quota, _))) = quotas
+        .iter()
+        .find(|(_, (quota, incoming))| incoming > quota)
+    {
+        return Err(query_error(
+            QueryError::OverQuota(name.clone(), *quota),
+            "Could not log data",
+        ));
+    }
+
+    let Inserted {
+        logs: inserted,
+        evicted,
+    } = if fresh.is_empty() {
+        Inserted::default()
+    } else {
+        state
+            .store
+            .insert(fresh, quotas, config.quota_action)
+            .await
+            .map_err(|err| query_error(err, "Could not log data"))?
+    };
+
+    state.metrics.logs_ingested.inc_by(inserted.len() as u64);
+
+    if let Some(webhook) = &state.webhook {
+        for log in &inserted {
+            let payload = json!({ "tenant": caller, "log": log.clone().into_json() });
+
+            if webhook.try_send(payload).is_err() {
+                warn!("Webhook queue is full, not forwarding log {}", log.id);
+            }
+        }
+    }
+
+    let mut inserted = inserted.into_iter();
+
+    for (_, evicted) in evicted {
+        record_audit(
+            state,
+            AuditEntry::new(
+                "quota_evict",
+                Some(caller.clone()),
+                None,
+                evicted.len(),
+                evicted,
+            ),
+        )
+        .await;
+    }
+
+    let inserted_count = inserted.len();
+    let mut results = slots
+        .into_iter()
+        .filter_map(|slot| match slot {
+            Some(existing) => Some((existing, true)),
+            None => inserted.next().map(|log| (log, false)),
+        })
+        .collect::<Vec<_>>();
+    let deduped = results.iter().filter(|(_, deduped)| *deduped).count();
+
+    let mut response = match (batch, representation) {
+        (true, true) => {
+            let data = results
+                .into_iter()
+                .map(|(log, _)| log.into_json())
+                .collect::<Vec<_>>();
+
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "inserted": inserted_count, "data": data })
+        }
+        (true, false) => {
+            let ids = results.iter().map(|(log, _)| log.id).collect::<Vec<_>>();
+
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "inserted": inserted_count, "ids": ids })
+        }
+        (false, true) => {
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": results.remove(0).0.into_json() })
+        }
+        (false, false) => {
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "id": results[0].0.id.to_string() })
+        }
+    };
+
+    if dedupe {
+        response["deduped"] = if batch {
+            json!(deduped)
+        } else {
+            json!(deduped > 0)
+        };
+    }
+
+    if let Some(reservation) = reservation {
+        reservation.complete(response.clone());
+    }
+
+    Ok(Json(response))
+}
+
+fn quota_usage(config: &Config, rows: &[Log]) -> BTreeMap<String, (i64, i64)> {
+    let mut usage = BTreeMap::new();
+
+    for row in rows {
+        if let Some(quota) = config.quotas.get(&row.name) {
+            usage.entry(row.name.clone()).or_insert((*quota, 0)).1 += 1;
+        }
+    }
+
+    usage
+}
+
+fn content_hash(name: &str, data: &serde_json::Value) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(name.as_bytes());
+    hasher.update([0]);
+    hasher.update(data.to_string().as_bytes());
+
+    hex::encode(hasher.finalize())
+}
+
+fn prefers_representation(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(HeaderName::from_static(PREFER_HEADER))
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|preference| {
+            preference
+                .trim()
+                .eq_ignore_ascii_case("return=representation")
+        })
+}
+
+fn reserve_idempotent(state: &AppState, key: &str) -> Result<Option<serde_json::Value>, ApiError> {
+    let mut responses = state.idempotency.lock().unwrap();
+
+    if responses.len() > IDEMPOTENCY_MAX_TRACKED {
+        responses.retain(|_, (stored, _)| stored.elapsed() < IDEMPOTENCY_TTL);
+    }
+
+    match responses.get(key) {
+        Some((stored, Some(response))) if stored.elapsed() < IDEMPOTENCY_TTL => {
+            Ok(Some(response.clone()))
+        }
+        Some((stored, None)) if stored.elapsed() < IDEMPOTENCY_TTL => Err(ApiError::Conflict(
+            "A request with this Idempotency-Key is still in progress",
+        )),
+        _ => {
+            responses.insert(key.to_string(), (Instant::now(), None));
+            Ok(None)
+        }
+    }
+}
+
+struct IdempotencyReservation<'a> {
+    state: &'a AppState,
+    key: String,
+}
+
+impl IdempotencyReservation<'_> {
+    fn complete(self, response: serde_json::Value) {
+        self.state
+            .idempotency
+            .lock()
+            .unwrap()
+            .insert(self.key.clone(), (Instant::now(), Some(response)));
+    }
+}
+
+impl Drop for IdempotencyReservation<'_> {
+    fn drop(&mut self) {
+        let mut responses = self.state.idempotency.lock().unwrap();
+
+        if let Some((_, None)) = responses.get(&self.key) {
+            responses.remove(&self.key);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GivemeRequest {
+    all: Option<bool>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    name: Option<String>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+    order: Option<String>,
+    #[param(value_type = Option<String>)]
+    before: Option<Cursor>,
+    format: Option<String>,
+    envelope: Option<bool>,
+    time_format: Option<String>,
+    fields: Option<String>,
+    level: Option<String>,
+    data_key: Option<String>,
+    data_value: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, ToSchema)]
+pub struct LogQuery {
+    all: Option<bool>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    name: Option<String>,
+    level: Option<String>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+    #[schema(value_type = Option<String>)]
+    before: Option<Cursor>,
+    #[serde(default)]
+    data: HashMap<String, String>,
+    search: Option<String>,
+    order: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchRequest {
+    q: String,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    name: Option<String>,
+    level: Option<String>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+    #[param(value_type = Option<String>)]
+    before: Option<Cursor>,
+    order: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/giveme",
+    tag = "logs",
+    params(GivemeRequest),
+    responses(
+        (status = 200, description = "Matching logs, newest first", headers(("X-Total-Count" = i64, description = "Number of matching logs across all pages")), body = Object, example = json!({ "status": 200, "message": "OK", "data": [], "next_cursor": null })),
+        (status = 304, description = "Not modified since the given ETag"),
+        (status = 400, description = "Invalid query"),
+        (status = 413, description = "all=true matched more than MAX_ALL_ROWS logs"),
+    ),
+    security(("key" = [])),
+)]
+async fn giveme(
+    query: Result<Query<GivemeRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    state.metrics.giveme_requests.inc();
+
+    let Query(query) = query?;
+
+    let data = match (query.data_key, query.data_value) {
+        (Some(data_key), Some(data_value)) => HashMap::from([(data_key, data_value)]),
+        (None, None) => HashMap::new(),
+        _ => {
+            return Err(ApiError::BadRequest(
+                "data_key and data_value must be given together".into(),
+            ));
+        }
+    };
+
+    let format = match query.format.as_deref() {
+        None | Some("json") if query.envelope.unwrap_or(true) => LogsFormat::Envelope,
+        None | Some("json") => LogsFormat::Bare,
+        Some("ndjson") => LogsFormat::Ndjson,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Invalid format, expected json or ndjson".into(),
+            ));
+        }
+    };
+
+    let times = match query.time_format.as_deref() {
+        None | Some("rfc3339") => TimeFormat::Rfc3339,
+        Some("epoch_ms") => TimeFormat::EpochMs,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Invalid time_format, expected rfc3339 or epoch_ms".into(),
+            ));
+        }
+    };
+
+    let fields = query
+        .fields
+        .as_deref()
+        .map(parse_fields)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    run_log_query(
+        &state,
+        caller,
+        &headers,
+        LogQuery {
+            all: query.all,
+            offset: query.offset,
+            limit: query.limit,
+            name: query.name,
+            level: query.level,
+            created_after: query.created_after,
+            created_before: query.created_before,
+            before: query.before,
+            data,
+            search: None,
+            order: query.order,
+        },
+        format,
+        times,
+        fields,
+    )
+    .await
+}
+
+fn parse_fields(value: &str) -> Result<Vec<&'static str>, String> {
+    value
+        .split(',')
+        .map(|field| {
+            LOG_FIELDS
+                .into_iter()
+                .find(|known| *known == field.trim())
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid field \"{field}\", expected one of {}",
+                        LOG_FIELDS.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    post,
+    path = "/query",
+    tag = "logs",
+    request_body = LogQuery,
+    responses(
+        (status = 200, description = "Matching logs", headers(("X-Total-Count" = i64, description = "Number of matching logs across all pages")), body = Object, example = json!({ "status": 200, "message": "OK", "data": [], "next_cursor": null })),
+        (status = 304, description = "Not modified since the given ETag"),
+        (status = 400, description = "Invalid query"),
+        (status = 413, description = "all=true matched more than MAX_ALL_ROWS logs"),
+    ),
+    security(("key" = [])),
+)]
+async fn query_logs(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    headers: HeaderMap,
+    query: Result<Json<LogQuery>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(query) = query?;
+
+    run_log_query(
+        &state,
+        caller,
+        &headers,
+        query,
+        LogsFormat::Envelope,
+        TimeFormat::Rfc3339,
+        None,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/logs/search",
+    tag = "logs",
+    params(SearchRequest),
+    responses(
+        (status = 200, description = "Logs whose data contains q", headers(("X-Total-Count" = i64, description = "Number of matching logs across all pages")), body = Object, example = json!({ "status": 200, "message": "OK", "data": [], "next_cursor": null })),
+        (status = 304, description = "Not modified since the given ETag"),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    headers: HeaderMap,
+    query: Result<Query<SearchRequest>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+
+    if query.q.is_empty() {
+        return Err(ApiError::BadRequest("Expected q".into()));
+    }
+
+    run_log_query(
+        &state,
+        caller,
+        &headers,
+        LogQuery {
+            offset: query.offset,
+            limit: query.limit,
+            name: query.name,
+            level: query.level,
+            created_after: query.created_after,
+            created_before: query.created_before,
+            before: query.before,
+            search: Some(query.q),
+            order: query.order,
+            ..Default::default()
+        },
+        LogsFormat::Envelope,
+        TimeFormat::Rfc3339,
+        None,
+    )
+    .await
+}
+
+fn like_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{escaped}%")
+}
+
+enum LogsFormat {
+    Envelope,
+    Bare,
+    Ndjson,
+}
+
+async fn run_log_query(
+    state: &AppState,
+    caller: String,
+    headers: &HeaderMap,
+    query: LogQuery,
+    format: LogsFormat,
+    times: TimeFormat,
+    fields: Option<Vec<&'static str>>,
+) -> Result<Response, ApiError> {
+    let config = state.config();
+    let limit = config.limit;
+
+    if !valid_page(query.offset, query.limit) {
+        return Err(ApiError::BadRequest("Invalid offset or limit".into()));
+    }
+
+    if !valid_level(query.level.as_deref()) {
+        return Err(ApiError::BadRequest(invalid_level_message()));
+    }
+
+    let ascending = match query.order.as_deref() {
+        None | Some("desc") => false,
+        Some("asc") => true,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Invalid order, expected asc or desc".into(),
+            ));
+        }
+    };
+
+    if config.compress_threshold.is_some() && (!query.data.is_empty() || query.search.is_some()) {
+        return Err(ApiError::BadRequest(
+            "data_key/data_value and search filters are unavailable while COMPRESS_THRESHOLD_BYTES is set".into(),
+        ));
+    }
+
+    if ascending && query.before.is_some() {
+        return Err(ApiError::BadRequest(
+            "before pages newest first and cannot be combined with order=asc".into(),
+        ));
+    }
+
+    if let LogsFormat::Ndjson = format {
+        return Ok(stream_ndjson(
+            state, caller, query, limit, ascending, times, fields,
+        ));
+    }
+
+    let paged = !query.all.unwrap_or(false);
+    let page_offset = query.offset.unwrap_or(0);
+    let page_limit = query.limit.map_or(limit, |l| l.min(limit));
+    let fetch_limit = if paged {
+        page_limit.saturating_add(1)
+    } else {
+        config.max_all_rows.saturating_add(1)
+    };
+    let query = LogQuery {
+        all: None,
+        offset: paged.then_some(page_offset),
+        limit: Some(fetch_limit),
+        ..query
+    };
+
+    let too_large = || {
+        ApiError::PayloadTooLarge(format!(
+            "More than {} logs match all=true, page with offset/limit or before, or use format=ndjson",
+            config.max_all_rows
+        ))
+    };
+
+    let (mut res, total) = if paged {
+        tokio::try_join!(
+            state
+                .store
+                .query(caller.clone(), query.clone(), fetch_limit, ascending),
+            state.store.count(caller, query),
+        )
+        .map_err(|err| query_error(err, "Could not get data"))?
+    } else {
+        let total = state
+            .store
+            .count(caller.clone(), query.clone())
+            .await
+            .map_err(|err| query_error(err, "Could not get data"))?;
+
+        if total > config.max_all_rows {
+            return Err(too_large());
+        }
+
+        let res = state
+            .store
+            .query(caller, query, fetch_limit, ascending)
+            .await
+            .map_err(|err| query_error(err, "Could not get data"))?;
+
+        (res, total)
+    };
+
+    if !paged && res.len() as i64 > config.max_all_rows {
+        return Err(too_large());
+    }
+
+    let has_more = paged && res.len() as i64 > page_limit;
+
+    if has_more {
+        res.truncate(page_limit as usize);
+    }
+
+    let next_cursor = res
+        .last()
+        .filter(|_| !ascending)
+        .map(|log| resume_token((log.created, log.id)));
+    let meta = json!({
+        "count": res.len(),
+        "limit": paged.then_some(page_limit),
+        "offset": paged.then_some(page_offset),
+        "has_more": has_more,
+    });
+    let mut response = Vec::<serde_json::Value>::with_capacity(res.len());
+
+    for log in res.into_iter() {
+        response.push(project(log.into_json_with(times), fields.as_deref()));
+    }
+
+    let body = match format {
+        LogsFormat::Bare => json!(response),
+        _ => {
+            json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": response, "next_cursor": next_cursor, "meta": meta })
+        }
+    };
+
+    let body = serde_json::to_vec(&body).map_err(|err| {
+        error!("{err}");
+        ApiError::Internal("Could not encode data")
+    })?;
+
+    let etag = weak_etag(&body);
+
+    let total_count = HeaderName::from_static(TOTAL_COUNT_HEADER);
+
+    if etag_matches(headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (total_count, total.to_string())],
+        )
+            .into_response());
+    }
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::ETAG, etag),
+            (total_count, total.to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn weak_etag(body: &[u8]) -> String {
+    format!("W/\"{}\"", hex::encode(&Sha256::digest(body)[..16]))
+}
+
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| {
+            candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        })
+}
+
+fn build_log_query(caller: String, query: LogQuery, limit: i64, ascending: bool) -> BoxedLogs {
+    use self::logs::dsl::*;
+
+    let all = query.all.unwrap_or(false);
+    let page_offset = query.offset.unwrap_or(0);
+    let page_limit = query.limit.map_or(limit, |l| l.min(limit));
+    let mut q = filter_logs(caller, query);
+
+    q = if ascending {
+        q.order((created.asc(), id.asc()))
+    } else {
+        q.order((created.desc(), id.desc()))
+    };
+
+    if !all {
+        q = q.offset(page_offset).limit(page_limit);
+    }
+
+    q
+}
+
+fn filter_logs(caller: String, query: LogQuery) -> BoxedLogs {
+    use self::logs::dsl::*;
+
+    let mut q = logs
+        .select(Log::as_select())
+        .filter(tenant.eq(caller))
+        .filter(deleted_at.is_null())
+        .into_boxed();
+
+    if let Some(requested_name) = query.name {
+        q = q.filter(name.eq(requested_name));
+    }
+
+    if let Some(requested_level) = query.level {
+        q = q.filter(level.eq(requested_level));
+    }
+
+    for (data_key, data_value) in query.data {
+        let scalar = serde_json::from_str::<serde_json::Value>(&data_value)
+            .ok()
+            .filter(|value| value.is_number() || value.is_boolean());
+        let text = json!({ &data_key: data_value });
+
+        q = match scalar {
+            Some(scalar) => q.filter(
+                sql::<Bool>("(data @> ")
+                    .bind::<Jsonb, _>(text)
+                    .sql(" OR data @> ")
+                    .bind::<Jsonb, _>(json!({ data_key: scalar }))
+                    .sql(")"),
+            ),
+            None => q.filter(sql::<Bool>("data @> ").bind::<Jsonb, _>(text)),
+        };
+    }
+
+    if let Some(search) = query.search {
+        q = q.filter(
+            sql::<Bool>("data::text ILIKE ")
+                .bind::<Text, _>(like_pattern(&search))
+                .sql(" ESCAPE '\\'"),
+        );
+    }
+
+    if let Some(after) = query.created_after {
+        q = q.filter(created.ge(after));
+    }
+
+    if let Some(before) = query.created_before {
+        q = q.filter(created.le(before));
+    }
+
+    if let Some(Cursor((cursor_created, cursor_id))) = query.before {
+        q = q.filter(
+            created
+                .lt(cursor_created)
+                .or(created.eq(cursor_created).and(id.lt(cursor_id))),
+        );
+    }
+
+    q
+}
+
+fn stream_ndjson(
+    state: &AppState,
+    caller: String,
+    query: LogQuery,
+    limit: i64,
+    ascending: bool,
+    times: TimeFormat,
+    fields: Option<Vec<&'static str>>,
+) -> Response {
+    let (offset, limit) = if query.all.unwrap_or(false) {
+        (0, None)
+    } else {
+        (
+            query.offset.unwrap_or(0),
+            Some(query.limit.map_or(limit, |l| l.min(limit))),
+        )
+    };
+    let format_row = move |log: Log| {
+        format!(
+            "{}\n",
+            project(log.into_json_with(times), fields.as_deref())
+        )
+    };
+    let scan = Scan {
+        query,
+        after: None,
+        through: None,
+        ascending,
+    };
+    let body = stream_logs(state, caller, scan, offset, limit, None, format_row);
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        body,
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/stream",
+    tag = "logs",
+    responses(
+        (status = 200, description = "Server-sent events, one per new log", content_type = "text/event-stream", body = Log),
+    ),
+    security(("key" = [])),
+)]
+async fn stream(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let logs = BroadcastStream::new(state.live.subscribe())
+        .filter_map(move |log| match log {
+            Ok((tenant, log)) if tenant == caller => Some(log),
+            _ => None,
+        })
+        .map(Some);
+    let stop = WatchStream::from_changes(state.shutdown.subscribe()).map(|_| None);
+
+    Sse::new(
+        logs.merge(stop)
+            .map_while(|log| log)
+            .map(|log| Event::default().event("log").json_data(log)),
+    )
+    .keep_alive(KeepAlive::default())
+}
+
+async fn listen_for_logs(url: String, notified: tokio::sync::mpsc::Sender<Uuid>) {
+    let mut attempt = 0;
+
+    loop {
+        match listen(&url, &notified, &mut attempt).await {
+            Ok(()) => return,
+            Err(err) => warn!("{err}"),
+        }
+
+        attempt += 1;
+        tokio::time::sleep(backoff_delay(attempt)).await;
+    }
+}
+
+async fn listen(
+    url: &str,
+    notified: &tokio::sync::mpsc::Sender<Uuid>,
+    attempt: &mut u32,
+) -> Result<(), String> {
+    let unavailable = |err: String| format!("Could not listen on {LIVE_CHANNEL}: {err}");
+    let (client, mut connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .map_err(|err| unavailable(err.to_string()))?;
+    let listen = format!("LISTEN {LIVE_CHANNEL}");
+    let notifications = forward_notifications(&mut connection, notified);
+
+    tokio::pin!(notifications);
+
+    tokio::select! {
+        listened = client.batch_execute(&listen) => {
+            listened.map_err(|err| unavailable(err.to_string()))?;
+        }
+        forwarded = &mut notifications => return forwarded.map_err(unavailable),
+    }
+
+    info!("Listening for new logs on {LIVE_CHANNEL}");
+    *attempt = 0;
+
+    notifications
+        .await
+        .map_err(|err| format!("Lost {LIVE_CHANNEL} listener: {err}"))
+}
+
+async fn forward_notifications(
+    connection: &mut tokio_postgres::Connection<Socket, NoTlsStream>,
+    notified: &tokio::sync::mpsc::Sender<Uuid>,
+) -> Result<(), String> {
+    loop {
+        match std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+            Some(Ok(AsyncMessage::Notification(notification))) => {
+                if let Ok(log_id) = notification.payload().parse() {
+                    if notified.send(log_id).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            Some(Ok(_)) => {}
+            Some(Err(err)) => return Err(err.to_string()),
+            None => return Err("connection closed".to_string()),
+        }
+    }
+}
+
+async fn republish_logs(
+    state: Arc<AppState>,
+    db: Arc<Pool>,
+    mut notifications: tokio::sync::mpsc::Receiver<Uuid>,
+) {
+    while let Some(log_id) = notifications.recv().await {
+        let mut log_ids = vec![log_id];
+
+        while log_ids.len() < MAX_BATCH_SIZE {
+            match notifications.try_recv() {
+                Ok(log_id) => log_ids.push(log_id),
+                Err(_) => break,
+            }
+        }
+
+        if state.live.receiver_count() == 0 {
+            continue;
+        }
+
+        match load_logs(&state, &db, log_ids).await {
+            Ok(rows) => {
+                for row in rows {
+                    let _ = state.live.send((row.tenant.clone(), row.into_json()));
+                }
+            }
+            Err(err) => error!("Could not load new logs: {err}"),
+        }
+    }
+}
+
+async fn load_logs(state: &AppState, db: &Pool, log_ids: Vec<Uuid>) -> Result<Vec<Log>, String> {
+    use self::logs::dsl::{created, id, logs};
+
+    let conn = db.get().await.map_err(|err| err.to_string())?;
+
+    interact(conn, state.config().query_timeout, move |conn| {
+        logs.filter(id.eq_any(log_ids))
+            .order((created.asc(), id.asc()))
+            .select(Log::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
+
+fn stream_logs(
+    state: &AppState,
+    caller: String,
+    mut scan: Scan,
+    offset: i64,
+    limit: Option<i64>,
+    header: Option<String>,
+    format_row: impl Fn(Log) -> String + Send + 'static,
+) -> Body {
+    let store = state.store.clone();
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, std::io::Error>>(STREAM_BUFFER);
+
+    tokio::spawn(async move {
+        if let Some(header) = header {
+            if tx.send(Ok(header)).await.is_err() {
+                return;
+            }
+        }
+
+        let mut offset = offset;
+        let mut remaining = limit;
+
+        while remaining != Some(0) {
+            let chunk = remaining.map_or(STREAM_CHUNK, |remaining| remaining.min(STREAM_CHUNK));
+            let rows = match store
+                .scan(caller.clone(), scan.clone(), offset, chunk)
+                .await
+            {
+                Ok(rows) => rows,
+                Err(err) => {
+                    error!("{err}");
+                    let _ = tx.send(Err(std::io::Error::other(err.to_string()))).await;
+                    return;
+                }
+            };
+            let fetched = rows.len() as i64;
+
+            scan.after = rows.last().map(|log| (log.created, log.id));
+            offset = 0;
+            remaining = remaining.map(|remaining| remaining - fetched);
+
+            for log in rows {
+                if tx.send(Ok(format_row(log))).await.is_err() {
+                    return;
+                }
+            }
+
+            if fetched < chunk {
+                break;
+            }
+        }
+    });
+
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportRequest {
+    name: Option<String>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/export.csv",
+    tag = "logs",
+    params(ExportRequest),
+    responses(
+        (status = 200, description = "Matching logs as CSV", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn export_csv(
+    query: Result<Query<ExportRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let query = LogQuery {
+        name: query.name,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        ..Default::default()
+    };
+    let scan = Scan {
+        query,
+        after: None,
+        through: None,
+        ascending: true,
+    };
+    let body = stream_logs(
+        &state,
+        caller,
+        scan,
+        0,
+        None,
+        Some("id,name,data,created\n".to_string()),
+        |log| {
+            format!(
+                "{},{},{},{}\n",
+                log.id,
+                csv_field(&log.name),
+                csv_field(&log.data.to_string()),
+                log.created
+                    .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            )
+        },
+    );
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"logs.csv\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BackupRequest {
+    after: Option<String>,
+    limit: Option<i64>,
+}
+
+type Position = (chrono::DateTime<chrono::Utc>, Uuid);
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "logs",
+    params(BackupRequest),
+    responses(
+        (status = 200, description = "Logs after the resume token in (created, id) order as NDJSON", content_type = "application/x-ndjson", body = Log,
+            headers(("x-resume-token" = String, description = "Pass as after to continue the export, logs later inserted with an earlier created are not revisited"))),
+        (status = 400, description = "Invalid after or limit"),
+    ),
+    security(("key" = [])),
+)]
+async fn export(
+    query: Result<Query<BackupRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+
+    if !valid_page(None, query.limit) {
+        return Err(ApiError::BadRequest("Invalid limit".into()));
+    }
+
+    let after = query
+        .after
+        .as_deref()
+        .map(|token| {
+            parse_resume_token(token).ok_or(ApiError::BadRequest(format!(
+                "Invalid after, expected a token from {RESUME_TOKEN_HEADER}"
+            )))
+        })
+        .transpose()?;
+    let max_all_rows = state.config().max_all_rows;
+    let limit = query.limit.map_or(max_all_rows, |l| l.min(max_all_rows));
+
+    let scan = Scan {
+        query: LogQuery::default(),
+        after,
+        through: None,
+        ascending: true,
+    };
+    let nth = state
+        .store
+        .scan(caller.clone(), scan.clone(), limit - 1, 1)
+        .await
+        .map_err(|err| query_error(err, "Could not export data"))?;
+    let last = match nth.first() {
+        Some(log) => Some((log.created, log.id)),
+        None => state
+            .store
+            .scan(
+                caller.clone(),
+                Scan {
+                    after: None,
+                    ascending: false,
+                    ..scan.clone()
+                },
+                0,
+                1,
+            )
+            .await
+            .map_err(|err| query_error(err, "Could not export data"))?
+            .first()
+            .map(|log| (log.created, log.id))
+            .filter(|newest| after.is_none_or(|after| *newest > after)),
+    };
+
+    let Some(last) = last.or(after) else {
+        return Ok((
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+        )
+            .into_response());
+    };
+
+    let scan = Scan {
+        through: Some(last),
+        ..scan
+    };
+    let body = stream_logs(&state, caller, scan, 0, None, None, |log| {
+        format!("{}\n", log.into_json())
+    });
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                HeaderName::from_static(RESUME_TOKEN_HEADER),
+                resume_token(last),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn resume_token((created, id): Position) -> String {
+    format!(
+        "{},{id}",
+        created.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+    )
+}
+
+fn parse_resume_token(token: &str) -> Option<Position> {
+    let (created, id) = token.split_once(',')?;
+
+    Some((
+        chrono::DateTime::parse_from_rfc3339(created).ok()?.into(),
+        Uuid::parse_str(id).ok()?,
+    ))
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cursor(Position);
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let token = String::deserialize(deserializer)?;
+
+        parse_resume_token(&token)
+            .map(Cursor)
+            .ok_or_else(|| serde::de::Error::custom("expected a next_cursor token"))
+    }
+}
+
+fn valid_name(config: &Config, name: &str) -> bool {
+    !name.is_empty() && name.chars().count() <= config.max_name_length
+}
+
+fn valid_level(requested: Option<&str>) -> bool {
+    requested.is_none_or(|requested| LEVELS.contains(&requested))
+}
+
+fn invalid_level_message() -> String {
+    format!("Invalid level, expected one of {}", LEVELS.join(", "))
+}
+
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[utoipa::path(
+    get,
+    path = "/log/{id}",
+    tag = "logs",
+    params(("id" = Uuid, Path, description = "Log id")),
+    responses(
+        (status = 200, description = "The log", body = Object, example = json!({ "status": 200, "message": "OK", "data": {} })),
+        (status = 404, description = "Not found"),
+    ),
+    security(("key" = [])),
+)]
+async fn get_log(
+    log_id: Result<Path<Uuid>, PathRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Path(log_id) = log_id?;
+    let log = state
+        .store
+        .get(caller, log_id)
+        .await
+        .map_err(|err| query_error(err, "Could not get data"))?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": log.into_json() }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/log/{id}/data",
+    tag = "logs",
+    params(("id" = Uuid, Path, description = "Log id")),
+    responses(
+        (status = 200, description = "The log's data, without an envelope", body = Object, example = json!({ "user": 1 })),
+        (status = 404, description = "Not found"),
+    ),
+    security(("key" = [])),
+)]
+async fn get_log_data(
+    log_id: Result<Path<Uuid>, PathRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Path(log_id) = log_id?;
+    let log = state
+        .store
+        .get(caller, log_id)
+        .await
+        .map_err(|err| query_error(err, "Could not get data"))?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(log.data))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteRequest {
+    hard: Option<bool>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/log/{id}",
+    tag = "logs",
+    params(("id" = Uuid, Path, description = "Log id"), DeleteRequest),
+    responses(
+        (status = 200, description = "Log deleted", body = Object, example = json!({ "status": 200, "message": "OK", "hard": false })),
+        (status = 404, description = "Not found"),
+    ),
+    security(("key" = [])),
+)]
+async fn delete_log(
+    log_id: Result<Path<Uuid>, PathRejection>,
+    query: Result<Query<DeleteRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    Extension(KeyHash(key_hash)): Extension<KeyHash>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+    let Path(log_id) = log_id?;
+    let hard = query.hard.unwrap_or(false);
+
+    let deleted = state
+        .store
+        .delete(caller.clone(), log_id, hard)
+        .await
+        .map_err(|err| query_error(err, "Could not delete data"))?;
+
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    record_audit(
+        &state,
+        AuditEntry::new(
+            if hard { "hard_delete" } else { "delete" },
+            Some(caller),
+            Some(key_hash),
+            deleted,
+            vec![log_id],
+        ),
+    )
+    .await;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "deleted": deleted, "hard": hard }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/log/{id}/restore",
+    tag = "logs",
+    params(("id" = Uuid, Path, description = "Log id")),
+    responses(
+        (status = 200, description = "Log restored", body = Object, example = json!({ "status": 200, "message": "OK", "restored": 1 })),
+        (status = 404, description = "Not found"),
+    ),
+    security(("key" = [])),
+)]
+async fn restore_log(
+    log_id: Result<Path<Uuid>, PathRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Path(log_id) = log_id?;
+
+    let restored = state
+        .store
+        .restore(caller, log_id)
+        .await
+        .map_err(|err| query_error(err, "Could not restore data"))?;
+
+    if restored == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "restored": restored }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BulkDeleteRequest {
+    name: String,
+    hard: Option<bool>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/logs",
+    tag = "logs",
+    params(BulkDeleteRequest),
+    responses(
+        (status = 200, description = "Logs deleted", body = Object, example = json!({ "status": 200, "message": "OK", "deleted": 0, "hard": false })),
+        (status = 400, description = "Expected name"),
+    ),
+    security(("key" = [])),
+)]
+async fn delete_logs(
+    query: Result<Query<BulkDeleteRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    Extension(KeyHash(key_hash)): Extension<KeyHash>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    if query.name.is_empty() {
+        return Err(ApiError::BadRequest("Expected name".into()));
+    }
+
+    let hard = query.hard.unwrap_or(false);
+
+    let deleted_ids = state
+        .store
+        .delete_named(caller.clone(), query.name, hard)
+        .await
+        .map_err(|err| query_error(err, "Could not delete data"))?;
+    let deleted = deleted_ids.len();
+
+    record_audit(
+        &state,
+        AuditEntry::new(
+            if hard {
+                "bulk_hard_delete"
+            } else {
+                "bulk_delete"
+            },
+            Some(caller),
+            Some(key_hash),
+            deleted,
+            deleted_ids,
+        ),
+    )
+    .await;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "deleted": deleted, "hard": hard }),
+    ))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct LogPatch {
+    name: Option<String>,
+    #[schema(value_type = Option<Value>)]
+    data: Option<LogData>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = logs, treat_none_as_null = true)]
+struct LogChanges {
+    name: String,
+    data: serde_json::Value,
+    updated: Option<chrono::DateTime<chrono::Utc>>,
+    hash: Option<String>,
+    data_compressed: Option<Vec<u8>>,
+}
+
+impl From<Log> for LogChanges {
+    fn from(log: Log) -> Self {
+        LogChanges {
+            name: log.name,
+            data: log.data,
+            updated: log.updated,
+            hash: log.hash,
+            data_compressed: log.data_compressed,
+        }
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/log/{id}",
+    tag = "logs",
+    params(("id" = Uuid, Path, description = "Log id")),
+    request_body = LogPatch,
+    responses(
+        (status = 200, description = "Updated log", body = Object, example = json!({ "status": 200, "message": "OK", "data": {} })),
+        (status = 400, description = "Invalid patch"),
+        (status = 404, description = "Not found"),
+        (status = 422, description = "Patched data does not match the schema registered for its name"),
+    ),
+    security(("key" = [])),
+)]
+async fn patch_log(
+    log_id: Result<Path<Uuid>, PathRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    req_body: Result<Json<LogPatch>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let config = state.config();
+    let Path(log_id) = log_id?;
+    let Json(req_body) = req_body?;
+
+    if req_body.name.is_none() && req_body.data.is_none() {
+        return Err(ApiError::BadRequest(
+            "Expected name or data to update".into(),
+        ));
+    }
+
+    if req_body
+        .name
+        .as_deref()
+        .is_some_and(|name| !valid_name(&config, name))
+    {
+        return Err(ApiError::BadRequest("Invalid name".into()));
+    }
+
+    let (name, data) = match (req_body.name, req_body.data.map(|data| data.0)) {
+        (Some(name), Some(data)) => (Some(name), Some(data)),
+        (name, data) if config.schemas.is_empty() => (name, data),
+        (name, data) => {
+            let current = state
+                .store
+                .get(caller.clone(), log_id)
+                .await
+                .map_err(|err| query_error(err, "Could not update data"))?
+                .ok_or(ApiError::NotFound)?;
+
+            (
+                Some(name.unwrap_or(current.name)),
+                Some(data.unwrap_or(current.data)),
+            )
+        }
+    };
+
+    if let (Some(name), Some(data)) = (&name, &data) {
+        let errors = schema_failures(&config, 0, name, data);
+
+        if !errors.is_empty() {
+            return Err(ApiError::SchemaMismatch(errors));
+        }
+    }
+
+    let log = state
+        .store
+        .update(caller, log_id, name, data)
+        .await
+        .map_err(|err| query_error(err, "Could not update data"))?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": log.into_json() }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CountRequest {
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/count",
+    tag = "stats",
+    params(CountRequest),
+    responses(
+        (status = 200, description = "Number of logs", headers(("X-Total-Count" = i64, description = "Same as count")), body = Object, example = json!({ "status": 200, "message": "OK", "count": 0 })),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn count(
+    query: Result<Query<CountRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+
+    let total = state
+        .store
+        .count(
+            caller,
+            LogQuery {
+                name: query.name,
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|err| query_error(err, "Could not count data"))?;
+
+    Ok((
+        StatusCode::OK,
+        [(
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+            total.to_string(),
+        )],
+        Json(json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "count": total })),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/names",
+    tag = "stats",
+    responses(
+        (status = 200, description = "Distinct log names", body = Object, example = json!({ "status": 200, "message": "OK", "data": [] })),
+    ),
+    security(("key" = [])),
+)]
+async fn names(
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let names = state
+        .store
+        .names(caller)
+        .await
+        .map_err(|err| query_error(err, "Could not get data"))?;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "names": names }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LatestRequest {
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/latest",
+    tag = "logs",
+    params(LatestRequest),
+    responses(
+        (status = 200, description = "The newest log of each name, ordered by name", body = Object, example = json!({ "status": 200, "message": "OK", "data": [] })),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn latest(
+    query: Result<Query<LatestRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    let latest = state
+        .store
+        .latest(caller, query.name)
+        .await
+        .map_err(|err| query_error(err, "Could not get data"))?
+        .into_iter()
+        .map(Log::into_json)
+        .collect::<Vec<_>>();
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": latest }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsRequest {
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/by-name",
+    tag = "stats",
+    params(StatsRequest),
+    responses(
+        (status = 200, description = "Log counts per name", body = Object, example = json!({ "status": 200, "message": "OK", "counts": { "deploy": 2 } })),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn stats_by_name(
+    query: Result<Query<StatsRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    let counts = state
+        .store
+        .counts_by_name(caller, query.created_after, query.created_before)
+        .await
+        .map_err(|err| query_error(err, "Could not count data"))?;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "counts": counts.into_iter().collect::<HashMap<_, _>>() }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeseriesRequest {
+    bucket: Option<String>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(QueryableByName)]
+struct Bucket {
+    #[diesel(sql_type = Timestamptz)]
+    bucket: chrono::DateTime<chrono::Utc>,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats/timeseries",
+    tag = "stats",
+    params(TimeseriesRequest),
+    responses(
+        (status = 200, description = "Log counts per time bucket", body = Object, example = json!({ "status": 200, "message": "OK", "data": [] })),
+        (status = 400, description = "Invalid query"),
+    ),
+    security(("key" = [])),
+)]
+async fn stats_timeseries(
+    query: Result<Query<TimeseriesRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    let interval = match query.bucket.as_deref() {
+        None | Some("hour") => Interval::Hour,
+        Some("day") => Interval::Day,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Invalid bucket, expected hour or day".into(),
+            ));
+        }
+    };
+
+    let before = query.created_before.unwrap_or_else(chrono::Utc::now);
+    let after = query
+        .created_after
+        .unwrap_or_else(|| before - chrono::Duration::hours(24));
+
+    let buckets = state
+        .store
+        .timeseries(caller, interval, after, before)
+        .await
+        .map_err(|err| query_error(err, "Could not count data"))?
+        .into_iter()
+        .map(|(bucket, count)| json!({ "bucket": bucket, "count": count }))
+        .collect::<Vec<_>>();
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": buckets }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RetentionRequest {
+    days: i64,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/logs/retention",
+    tag = "logs",
+    params(RetentionRequest),
+    responses(
+        (status = 200, description = "Logs older than days removed", body = Object, example = json!({ "status": 200, "message": "OK", "deleted": 0 })),
+        (status = 400, description = "Invalid days"),
+    ),
+    security(("key" = [])),
+)]
+async fn retention(
+    query: Result<Query<RetentionRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+    Extension(KeyHash(key_hash)): Extension<KeyHash>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    let Some(cutoff) = retention_cutoff(query.days) else {
+        return Err(ApiError::BadRequest(
+            "Invalid days, expected an integer >= 1".into(),
+        ));
+    };
+
+    let deleted = state
+        .store
+        .delete_older_than(Some(caller.clone()), cutoff)
+        .await
+        .map_err(|err| query_error(err, "Could not delete data"))?;
+
+    record_audit(
+        &state,
+        AuditEntry::new(
+            "retention",
+            Some(caller),
+            Some(key_hash),
+            deleted,
+            Vec::new(),
+        ),
+    )
+    .await;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "deleted": deleted }),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditRequest {
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "logs",
+    params(AuditRequest),
+    responses(
+        (status = 200, description = "Destructive operations, newest first", body = Object, example = json!({ "status": 200, "message": "OK", "data": [] })),
+        (status = 400, description = "Invalid offset or limit"),
+    ),
+    security(("key" = [])),
+)]
+async fn audit_log(
+    query: Result<Query<AuditRequest>, QueryRejection>,
+    State(state): State<Arc<AppState>>,
+    Extension(Tenant(caller)): Extension<Tenant>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query?;
+
+    if !valid_page(query.offset, query.limit) {
+        return Err(ApiError::BadRequest("Invalid offset or limit".into()));
+    }
+
+    let limit = state.config().limit;
+    let entries = state
+        .store
+        .audit(
+            caller,
+            query.offset.unwrap_or(0),
+            query.limit.map_or(limit, |l| l.min(limit)),
+        )
+        .await
+        .map_err(|err| query_error(err, "Could not get audit log"))?;
+
+    Ok(Json(
+        json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": entries }),
+    ))
+}
+
+async fn forward_webhooks(
+    url: reqwest::Url,
+    secret: Option<Vec<u8>>,
+    mut queue: tokio::sync::mpsc::Receiver<serde_json::Value>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(err) => {
+            error!("Could not create webhook client: {err}");
+            return;
+        }
+    };
+
+    loop {
+        let payload = tokio::select! {
+            biased;
+            payload = queue.recv() => payload,
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                queue.close();
+                continue;
+            }
+        };
+        let Some(payload) = payload else {
+            info!("Webhook forwarding stopped");
+            return;
+        };
+        let body = payload.to_string();
+        let mut backoff = WEBHOOK_BACKOFF;
+
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            match post_webhook(&client, &url, secret.as_deref(), &body).await {
+                Ok(()) => break,
+                Err(err) if attempt < WEBHOOK_ATTEMPTS => {
+                    warn!("Webhook attempt {attempt} failed, retrying in {backoff:?}: {err}");
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(err) => error!("Webhook failed after {attempt} attempts: {err}"),
+            }
+        }
+    }
+}
+
+async fn post_webhook(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    secret: Option<&[u8]>,
+    body: &str,
+) -> Result<(), String> {
+    let mut request = client
+        .post(url.clone())
+        .header(header::CONTENT_TYPE, "application/json");
+
+    if let Some(secret) = secret {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|err| err.to_string())?;
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body.as_bytes());
+
+        request = request
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, hex::encode(mac.finalize().into_bytes()));
+    }
+
+    let response = request
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("Webhook responded with {}", status.as_u16())),
+    }
+}
+
+async fn retention_sweeps(state: Arc<AppState>, days: i64, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    let mut shutdown = state.shutdown.subscribe();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.wait_for(|stopping| *stopping) => {
+                info!("Retention sweeps stopped");
+                return;
+            }
+        }
+
+        if state.read_only.load(Ordering::Relaxed) {
+            info!("Skipping retention sweep while read-only mode is on");
+            continue;
+        }
+
+        match sweep_retention(&state, days).await {
+            Ok(deleted) => info!("Retention sweep deleted {deleted} logs"),
+            Err(err) => error!("Retention sweep failed: {err}"),
+        }
+    }
+}
+
+async fn sweep_retention(state: &AppState, days: i64) -> Result<usize, String> {
+    let cutoff = retention_cutoff(days).ok_or("retention cutoff out of range")?;
+    let deleted = state
+        .store
+        .delete_older_than(None, cutoff)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if deleted > 0 {
+        state
+            .store
+            .record_audit(AuditEntry::new(
+                "retention_sweep",
+                None,
+                None,
+                deleted,
+                Vec::new(),
+            ))
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(deleted)
+}
+
+fn retention_cutoff(days: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+    if days < 1 {
+        return None;
     }
 
-    Json(json!({ "status": StatusCode::OK.as_u16(), "message": "OK", "data": response }))
+    chrono::Duration::try_days(days).and_then(|days| chrono::Utc::now().checked_sub_signed(days))
 }
@@ -0,0 +1,850 @@
+use super::decompress_data;
+use super::interact;
+use super::like_pattern;
+use super::AuditEntry;
+use super::Cursor;
+use super::Inserted;
+use super::Interval;
+use super::Log;
+use super::LogQuery;
+use super::LogStore;
+use super::QueryError;
+use super::QuotaAction;
+use super::Scan;
+use super::SCHEMA_MISMATCH;
+use async_trait::async_trait;
+use deadpool::managed::Hook;
+use deadpool::managed::HookError;
+use deadpool_diesel::sqlite::Manager;
+use deadpool_diesel::sqlite::Object;
+use deadpool_diesel::sqlite::Pool;
+use deadpool_diesel::Connection;
+use diesel::dsl::count_star;
+use diesel::dsl::sql;
+use diesel::dsl::AsSelect;
+use diesel::dsl::SqlTypeOf;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Bool;
+use diesel::sql_types::Text;
+use diesel::sql_types::TimestamptzSqlite;
+use diesel::sqlite::Sqlite;
+use diesel::table;
+use diesel_migrations::embed_migrations;
+use diesel_migrations::EmbeddedMigrations;
+use diesel_migrations::MigrationHarness;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_sqlite");
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const NEWEST_OF_NAME: &str = "logs.id = (SELECT newest.id FROM logs AS newest \
+     WHERE newest.tenant = logs.tenant AND newest.name = logs.name AND newest.deleted_at IS NULL \
+     ORDER BY newest.created DESC, newest.id DESC LIMIT 1)";
+
+type BoxedLogs = logs::BoxedQuery<'static, Sqlite, SqlTypeOf<AsSelect<SqliteLog, Sqlite>>>;
+
+table! {
+    logs (id) {
+        id -> Text,
+        name -> Text,
+        data -> Text,
+        created -> TimestamptzSqlite,
+        level -> Text,
+        updated -> Nullable<TimestamptzSqlite>,
+        tenant -> Text,
+        deleted_at -> Nullable<TimestamptzSqlite>,
+        hash -> Nullable<Text>,
+        data_compressed -> Nullable<Binary>,
+    }
+}
+
+table! {
+    audit (id) {
+        id -> Text,
+        operation -> Text,
+        tenant -> Nullable<Text>,
+        key_hash -> Nullable<Text>,
+        affected -> BigInt,
+        ids -> Text,
+        created -> TimestamptzSqlite,
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable)]
+#[diesel(table_name = logs)]
+#[diesel(check_for_backend(Sqlite))]
+struct SqliteLog {
+    id: String,
+    name: String,
+    data: String,
+    created: chrono::DateTime<chrono::Utc>,
+    level: String,
+    updated: Option<chrono::DateTime<chrono::Utc>>,
+    tenant: String,
+    hash: Option<String>,
+    data_compressed: Option<Vec<u8>>,
+}
+
+impl From<&Log> for SqliteLog {
+    fn from(log: &Log) -> Self {
+        SqliteLog {
+            id: log.id.to_string(),
+            name: log.name.clone(),
+            data: log.data.to_string(),
+            created: log.created,
+            level: log.level.clone(),
+            updated: log.updated,
+            tenant: log.tenant.clone(),
+            hash: log.hash.clone(),
+            data_compressed: log.data_compressed.clone(),
+        }
+    }
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = logs, treat_none_as_null = true)]
+struct SqliteLogChanges {
+    name: String,
+    data: String,
+    updated: Option<chrono::DateTime<chrono::Utc>>,
+    hash: Option<String>,
+    data_compressed: Option<Vec<u8>>,
+}
+
+impl From<SqliteLog> for SqliteLogChanges {
+    fn from(row: SqliteLog) -> Self {
+        SqliteLogChanges {
+            name: row.name,
+            data: row.data,
+            updated: row.updated,
+            hash: row.hash,
+            data_compressed: row.data_compressed,
+        }
+    }
+}
+
+impl TryFrom<SqliteLog> for Log {
+    type Error = QueryError;
+
+    fn try_from(row: SqliteLog) -> Result<Self, Self::Error> {
+        let id = Uuid::parse_str(&row.id).map_err(|err| QueryError::Corrupt(err.to_string()))?;
+        let data = match &row.data_compressed {
+            Some(bytes) => decompress_data(bytes).map_err(QueryError::Corrupt)?,
+            None => serde_json::from_str(&row.data)
+                .map_err(|err| QueryError::Corrupt(err.to_string()))?,
+        };
+
+        Ok(Log {
+            id,
+            name: row.name,
+            data,
+            created: row.created,
+            level: row.level,
+            updated: row.updated,
+            tenant: row.tenant,
+            hash: row.hash,
+            data_compressed: None,
+        })
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable)]
+#[diesel(table_name = audit)]
+#[diesel(check_for_backend(Sqlite))]
+struct SqliteAuditEntry {
+    id: String,
+    operation: String,
+    tenant: Option<String>,
+    key_hash: Option<String>,
+    affected: i64,
+    ids: String,
+    created: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<AuditEntry> for SqliteAuditEntry {
+    fn from(entry: AuditEntry) -> Self {
+        SqliteAuditEntry {
+            id: entry.id.to_string(),
+            operation: entry.operation,
+            tenant: entry.tenant,
+            key_hash: entry.key_hash,
+            affected: entry.affected,
+            ids: entry.ids.to_string(),
+            created: entry.created,
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct Bucket {
+    #[diesel(sql_type = TimestamptzSqlite)]
+    bucket: chrono::DateTime<chrono::Utc>,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+impl TryFrom<SqliteAuditEntry> for AuditEntry {
+    type Error = QueryError;
+
+    fn try_from(row: SqliteAuditEntry) -> Result<Self, Self::Error> {
+        let id = Uuid::parse_str(&row.id).map_err(|err| QueryError::Corrupt(err.to_string()))?;
+        let ids =
+            serde_json::from_str(&row.ids).map_err(|err| QueryError::Corrupt(err.to_string()))?;
+
+        Ok(AuditEntry {
+            id,
+            operation: row.operation,
+            tenant: row.tenant,
+            key_hash: row.key_hash,
+            affected: row.affected,
+            ids,
+            created: row.created,
+        })
+    }
+}
+
+fn filter_logs(caller: String, query: LogQuery) -> BoxedLogs {
+    use self::logs::dsl::*;
+
+    let mut q = logs
+        .select(SqliteLog::as_select())
+        .filter(tenant.eq(caller))
+        .filter(deleted_at.is_null())
+        .into_boxed::<Sqlite>();
+
+    if let Some(requested_name) = query.name {
+        q = q.filter(name.eq(requested_name));
+    }
+
+    if let Some(requested_level) = query.level {
+        q = q.filter(level.eq(requested_level));
+    }
+
+    for (data_key, data_value) in query.data {
+        q = q.filter(
+            sql::<Bool>("CAST(data ->> ")
+                .bind::<Text, _>(data_key)
+                .sql(" AS TEXT) = ")
+                .bind::<Text, _>(data_value),
+        );
+    }
+
+    if let Some(search) = query.search {
+        q = q.filter(
+            sql::<Bool>("data LIKE ")
+                .bind::<Text, _>(like_pattern(&search))
+                .sql(" ESCAPE '\\'"),
+        );
+    }
+
+    if let Some(after) = query.created_after {
+        q = q.filter(created.ge(after));
+    }
+
+    if let Some(before) = query.created_before {
+        q = q.filter(created.le(before));
+    }
+
+    if let Some(Cursor((cursor_created, cursor_id))) = query.before {
+        let cursor_id = cursor_id.to_string();
+
+        q = q.filter(
+            created
+                .lt(cursor_created)
+                .or(created.eq(cursor_created).and(id.lt(cursor_id))),
+        );
+    }
+
+    q
+}
+
+pub struct SqliteStore {
+    db: Pool,
+    query_timeout: Duration,
+    compress_threshold: Option<usize>,
+    live: broadcast::Sender<(String, serde_json::Value)>,
+}
+
+impl SqliteStore {
+    pub async fn open(
+        path: &str,
+        pool_size: usize,
+        query_timeout: Duration,
+        compress_threshold: Option<usize>,
+        live: broadcast::Sender<(String, serde_json::Value)>,
+    ) -> Result<Self, String> {
+        let manager = Manager::new(path, deadpool_diesel::Runtime::Tokio1);
+        let db = Pool::builder(manager)
+            .max_size(pool_size)
+            .runtime(deadpool_diesel::Runtime::Tokio1)
+            .post_create(Hook::async_fn(
+                |conn: &mut Connection<SqliteConnection>, _| {
+                    Box::pin(async move {
+                        conn.interact(|conn| {
+                            diesel::sql_query(format!(
+                                "PRAGMA busy_timeout = {}",
+                                BUSY_TIMEOUT.as_millis()
+                            ))
+                            .execute(conn)
+                        })
+                        .await
+                        .map_err(|err| HookError::Message(err.to_string()))?
+                        .map_err(|err| HookError::Message(err.to_string()))?;
+
+                        Ok(())
+                    })
+                },
+            ))
+            .build()
+            .map_err(|err| err.to_string())?;
+        let store = SqliteStore {
+            db,
+            query_timeout,
+            compress_threshold,
+            live,
+        };
+
+        let conn = store.conn().await.map_err(|err| err.to_string())?;
+
+        interact(conn, query_timeout, |conn| {
+            diesel::sql_query("PRAGMA journal_mode = WAL")
+                .execute(conn)
+                .map_err(|err| err.to_string())?;
+
+            conn.run_pending_migrations(MIGRATIONS)
+                .map_err(|err| err.to_string())?;
+
+            logs::table
+                .select(SqliteLog::as_select())
+                .limit(1)
+                .load(conn)
+                .map(|_| ())
+                .map_err(|err| format!("{SCHEMA_MISMATCH}: {err}"))
+        })
+        .await
+        .map_err(|err| err.to_string())??;
+
+        Ok(store)
+    }
+
+    async fn conn(&self) -> Result<Object, QueryError> {
+        self.db
+            .get()
+            .await
+            .map_err(|err| QueryError::Unavailable(err.to_string()))
+    }
+}
+
+#[async_trait]
+impl LogStore for SqliteStore {
+    async fn ping(&self) -> Result<(), QueryError> {
+        let conn = self.conn().await?;
+
+        interact(conn, self.query_timeout, |conn| {
+            diesel::sql_query("SELECT 1").execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    fn status(&self) -> deadpool::Status {
+        self.db.status()
+    }
+
+    async fn insert(
+        &self,
+        rows: Vec<Log>,
+        quotas: BTreeMap<String, (i64, i64)>,
+        action: QuotaAction,
+    ) -> Result<Inserted, QueryError> {
+        let conn = self.conn().await?;
+        let ids = rows
+            .iter()
+            .map(|row| row.id.to_string())
+            .collect::<Vec<_>>();
+        let tenant = rows
+            .first()
+            .map(|row| row.tenant.clone())
+            .unwrap_or_default();
+        let values = rows
+            .iter()
+            .map(|row| SqliteLog::from(&row.stored(self.compress_threshold)))
+            .collect::<Vec<_>>();
+
+        let evicted = interact(conn, self.query_timeout, move |conn| {
+            conn.immediate_transaction::<_, QueryError, _>(|conn| {
+                if action == QuotaAction::Reject {
+                    for (name, (quota, incoming)) in &quotas {
+                        let stored = logs::table
+                            .filter(logs::tenant.eq(&tenant))
+                            .filter(logs::name.eq(name))
+                            .count()
+                            .get_result::<i64>(conn)?;
+
+                        if stored + incoming > *quota {
+                            return Err(QueryError::OverQuota(name.clone(), *quota));
+                        }
+                    }
+                }
+
+                diesel::insert_into(logs::table)
+                    .values(&values)
+                    .execute(conn)?;
+
+                let mut evicted = Vec::new();
+
+                if action == QuotaAction::Evict {
+                    for (name, (quota, incoming)) in quotas {
+                        let oldest = logs::table
+                            .filter(logs::tenant.eq(&tenant))
+                            .filter(logs::name.eq(&name))
+                            .filter(logs::id.ne_all(&ids))
+                            .order((logs::created.desc(), logs::id.desc()))
+                            .offset(quota - incoming)
+                            .select(logs::id)
+                            .load::<String>(conn)?;
+
+                        diesel::delete(logs::table.filter(logs::id.eq_any(&oldest)))
+                            .execute(conn)?;
+                        evicted.push((name, oldest));
+                    }
+                }
+
+                Ok(evicted)
+            })
+        })
+        .await??;
+
+        let evicted = evicted
+            .into_iter()
+            .map(|(name, ids)| {
+                ids.iter()
+                    .map(|id| {
+                        Uuid::parse_str(id).map_err(|err| QueryError::Corrupt(err.to_string()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|ids| (name, ids))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.live.receiver_count() > 0 {
+            for row in &rows {
+                let _ = self
+                    .live
+                    .send((row.tenant.clone(), row.clone().into_json()));
+            }
+        }
+
+        Ok(Inserted {
+            logs: rows,
+            evicted,
+        })
+    }
+
+    async fn query(
+        &self,
+        caller: String,
+        query: LogQuery,
+        limit: i64,
+        ascending: bool,
+    ) -> Result<Vec<Log>, QueryError> {
+        use self::logs::dsl::*;
+
+        let conn = self.conn().await?;
+        let all = query.all.unwrap_or(false);
+        let page_offset = query.offset.unwrap_or(0);
+        let page_limit = query.limit.map_or(limit, |l| l.min(limit));
+        let mut q = filter_logs(caller, query);
+
+        q = if ascending {
+            q.order((created.asc(), id.asc()))
+        } else {
+            q.order((created.desc(), id.desc()))
+        };
+
+        if !all {
+            q = q.offset(page_offset).limit(page_limit);
+        }
+
+        let rows = interact(conn, self.query_timeout, move |conn| {
+            q.load::<SqliteLog>(conn)
+        })
+        .await??;
+
+        rows.into_iter().map(Log::try_from).collect()
+    }
+
+    async fn count(&self, caller: String, query: LogQuery) -> Result<i64, QueryError> {
+        let conn = self.conn().await?;
+        let q = filter_logs(caller, query);
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            q.count().get_result(conn)
+        })
+        .await??)
+    }
+
+    async fn names(&self, caller: String) -> Result<Vec<String>, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .select(logs::name)
+                .distinct()
+                .order(logs::name.asc())
+                .load(conn)
+        })
+        .await??)
+    }
+
+    async fn scan(
+        &self,
+        caller: String,
+        scan: Scan,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Log>, QueryError> {
+        use self::logs::dsl::{created, id};
+
+        let conn = self.conn().await?;
+        let mut q = filter_logs(caller, scan.query);
+
+        if let Some((after_created, after_id)) = scan.after {
+            let after_id = after_id.to_string();
+
+            q = if scan.ascending {
+                q.filter(
+                    created
+                        .gt(after_created)
+                        .or(created.eq(after_created).and(id.gt(after_id))),
+                )
+            } else {
+                q.filter(
+                    created
+                        .lt(after_created)
+                        .or(created.eq(after_created).and(id.lt(after_id))),
+                )
+            };
+        }
+
+        if let Some((through_created, through_id)) = scan.through {
+            let through_id = through_id.to_string();
+
+            q = if scan.ascending {
+                q.filter(
+                    created
+                        .lt(through_created)
+                        .or(created.eq(through_created).and(id.le(through_id))),
+                )
+            } else {
+                q.filter(
+                    created
+                        .gt(through_created)
+                        .or(created.eq(through_created).and(id.ge(through_id))),
+                )
+            };
+        }
+
+        let q = if scan.ascending {
+            q.order((created.asc(), id.asc()))
+        } else {
+            q.order((created.desc(), id.desc()))
+        }
+        .offset(offset)
+        .limit(limit);
+
+        let rows = interact(conn, self.query_timeout, move |conn| {
+            q.load::<SqliteLog>(conn)
+        })
+        .await??;
+
+        rows.into_iter().map(Log::try_from).collect()
+    }
+
+    async fn latest(&self, caller: String, name: Option<String>) -> Result<Vec<Log>, QueryError> {
+        let conn = self.conn().await?;
+        let query = LogQuery {
+            name,
+            ..Default::default()
+        };
+        let q = filter_logs(caller, query)
+            .filter(sql::<Bool>(NEWEST_OF_NAME))
+            .order(logs::name.asc());
+
+        let rows = interact(conn, self.query_timeout, move |conn| {
+            q.load::<SqliteLog>(conn)
+        })
+        .await??;
+
+        rows.into_iter().map(Log::try_from).collect()
+    }
+
+    async fn counts_by_name(
+        &self,
+        caller: String,
+        created_after: Option<chrono::DateTime<chrono::Utc>>,
+        created_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<(String, i64)>, QueryError> {
+        use self::logs::dsl::{created, deleted_at, logs, name, tenant};
+
+        let conn = self.conn().await?;
+        let mut q = logs
+            .filter(tenant.eq(caller))
+            .filter(deleted_at.is_null())
+            .group_by(name)
+            .select((name, count_star()))
+            .into_boxed();
+
+        if let Some(after) = created_after {
+            q = q.filter(created.ge(after));
+        }
+
+        if let Some(before) = created_before {
+            q = q.filter(created.le(before));
+        }
+
+        Ok(interact(conn, self.query_timeout, move |conn| q.load(conn)).await??)
+    }
+
+    async fn timeseries(
+        &self,
+        caller: String,
+        interval: Interval,
+        created_after: chrono::DateTime<chrono::Utc>,
+        created_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(chrono::DateTime<chrono::Utc>, i64)>, QueryError> {
+        let conn = self.conn().await?;
+        let bucket = match interval {
+            Interval::Hour => "%Y-%m-%d %H:00:00+00:00",
+            Interval::Day => "%Y-%m-%d 00:00:00+00:00",
+        };
+
+        let buckets = interact(conn, self.query_timeout, move |conn| {
+            diesel::sql_query(
+                "SELECT strftime(?, created) AS bucket, count(*) AS count FROM logs \
+                 WHERE tenant = ? AND deleted_at IS NULL AND created >= ? AND created <= ? GROUP BY bucket ORDER BY bucket",
+            )
+            .bind::<Text, _>(bucket)
+            .bind::<Text, _>(caller)
+            .bind::<TimestamptzSqlite, _>(created_after)
+            .bind::<TimestamptzSqlite, _>(created_before)
+            .load::<Bucket>(conn)
+        })
+        .await??;
+
+        Ok(buckets
+            .into_iter()
+            .map(|bucket| (bucket.bucket, bucket.count))
+            .collect())
+    }
+
+    async fn delete_older_than(
+        &self,
+        caller: Option<String>,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, QueryError> {
+        use self::logs::dsl::{created, logs, tenant};
+
+        let conn = self.conn().await?;
+
+        Ok(
+            interact(conn, self.query_timeout, move |conn| match caller {
+                Some(caller) => {
+                    diesel::delete(logs.filter(created.lt(cutoff)).filter(tenant.eq(caller)))
+                        .execute(conn)
+                }
+                None => diesel::delete(logs.filter(created.lt(cutoff))).execute(conn),
+            })
+            .await??,
+        )
+    }
+
+    async fn find_duplicates(
+        &self,
+        caller: String,
+        hashes: Vec<String>,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Log>, QueryError> {
+        let conn = self.conn().await?;
+
+        let rows = interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .filter(logs::hash.eq_any(hashes))
+                .filter(logs::created.ge(since))
+                .order((logs::created.desc(), logs::id.desc()))
+                .select(SqliteLog::as_select())
+                .load(conn)
+        })
+        .await??;
+
+        rows.into_iter().map(Log::try_from).collect()
+    }
+
+    async fn get(&self, caller: String, log_id: Uuid) -> Result<Option<Log>, QueryError> {
+        let conn = self.conn().await?;
+
+        let row = interact(conn, self.query_timeout, move |conn| {
+            logs::table
+                .find(log_id.to_string())
+                .filter(logs::tenant.eq(caller))
+                .filter(logs::deleted_at.is_null())
+                .select(SqliteLog::as_select())
+                .first(conn)
+                .optional()
+        })
+        .await??;
+
+        row.map(Log::try_from).transpose()
+    }
+
+    async fn update(
+        &self,
+        caller: String,
+        log_id: Uuid,
+        name: Option<String>,
+        data: Option<serde_json::Value>,
+    ) -> Result<Option<Log>, QueryError> {
+        let conn = self.conn().await?;
+        let threshold = self.compress_threshold;
+
+        interact(conn, self.query_timeout, move |conn| {
+            conn.immediate_transaction::<_, QueryError, _>(|conn| {
+                let Some(current) = logs::table
+                    .find(log_id.to_string())
+                    .filter(logs::tenant.eq(caller))
+                    .filter(logs::deleted_at.is_null())
+                    .select(SqliteLog::as_select())
+                    .first(conn)
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                let patched = Log::try_from(current)?.patched(name, data);
+                let row = SqliteLog::from(&patched.stored(threshold));
+
+                diesel::update(logs::table.find(log_id.to_string()))
+                    .set(SqliteLogChanges::from(row))
+                    .execute(conn)?;
+
+                Ok(Some(patched))
+            })
+        })
+        .await?
+    }
+
+    async fn delete(&self, caller: String, log_id: Uuid, hard: bool) -> Result<usize, QueryError> {
+        let conn = self.conn().await?;
+        let found = logs::table
+            .find(log_id.to_string())
+            .filter(logs::tenant.eq(caller));
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            if hard {
+                diesel::delete(found).execute(conn)
+            } else {
+                diesel::update(found.filter(logs::deleted_at.is_null()))
+                    .set(logs::deleted_at.eq(chrono::Utc::now()))
+                    .execute(conn)
+            }
+        })
+        .await??)
+    }
+
+    async fn delete_named(
+        &self,
+        caller: String,
+        name: String,
+        hard: bool,
+    ) -> Result<Vec<Uuid>, QueryError> {
+        let conn = self.conn().await?;
+
+        let deleted = interact(conn, self.query_timeout, move |conn| {
+            conn.immediate_transaction::<_, diesel::result::Error, _>(|conn| {
+                let found = logs::table
+                    .filter(logs::tenant.eq(caller))
+                    .filter(logs::name.eq(name));
+
+                if hard {
+                    let deleted = found.clone().select(logs::id).load::<String>(conn)?;
+
+                    diesel::delete(found).execute(conn)?;
+
+                    Ok(deleted)
+                } else {
+                    let found = found.filter(logs::deleted_at.is_null());
+                    let deleted = found.clone().select(logs::id).load::<String>(conn)?;
+
+                    diesel::update(found)
+                        .set(logs::deleted_at.eq(chrono::Utc::now()))
+                        .execute(conn)?;
+
+                    Ok(deleted)
+                }
+            })
+        })
+        .await??;
+
+        deleted
+            .iter()
+            .map(|id| Uuid::parse_str(id).map_err(|err| QueryError::Corrupt(err.to_string())))
+            .collect()
+    }
+
+    async fn restore(&self, caller: String, log_id: Uuid) -> Result<usize, QueryError> {
+        let conn = self.conn().await?;
+
+        Ok(interact(conn, self.query_timeout, move |conn| {
+            diesel::update(
+                logs::table
+                    .find(log_id.to_string())
+                    .filter(logs::tenant.eq(caller))
+                    .filter(logs::deleted_at.is_not_null()),
+            )
+            .set(logs::deleted_at.eq(None::<chrono::DateTime<chrono::Utc>>))
+            .execute(conn)
+        })
+        .await??)
+    }
+
+    async fn record_audit(&self, entry: AuditEntry) -> Result<(), QueryError> {
+        let conn = self.conn().await?;
+        let row = SqliteAuditEntry::from(entry);
+
+        interact(conn, self.query_timeout, move |conn| {
+            diesel::insert_into(audit::table).values(&row).execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    async fn audit(
+        &self,
+        caller: String,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, QueryError> {
+        let conn = self.conn().await?;
+
+        let rows = interact(conn, self.query_timeout, move |conn| {
+            audit::table
+                .filter(audit::tenant.eq(caller).or(audit::tenant.is_null()))
+                .order((audit::created.desc(), audit::id.desc()))
+                .offset(offset)
+                .limit(limit)
+                .select(SqliteAuditEntry::as_select())
+                .load(conn)
+        })
+        .await??;
+
+        rows.into_iter().map(AuditEntry::try_from).collect()
+    }
+}
@@ -0,0 +1,4001 @@
+use super::*;
+use axum::body::to_bytes;
+use chrono::DurationRound;
+use tokio::sync::MutexGuard;
+use tower::ServiceExt;
+
+const TEST_KEY: &str = "secret";
+
+static DB_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+struct TestDb {
+    url: String,
+    db: Arc<Pool>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+async fn test_db() -> Option<TestDb> {
+    let Ok(url) = env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is unset, skipping");
+        return None;
+    };
+    let lock = DB_LOCK.lock().await;
+    let db = Arc::new(test_pool(&url, DEFAULT_POOL_SIZE));
+
+    run_migrations(&db, DEFAULT_QUERY_TIMEOUT).await.unwrap();
+    truncate(&db).await;
+
+    Some(TestDb {
+        url,
+        db,
+        _lock: lock,
+    })
+}
+
+fn test_pool(url: &str, size: usize) -> Pool {
+    let manager = Manager::from_config(
+        url,
+        deadpool_diesel::Runtime::Tokio1,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Verified,
+        },
+    );
+
+    Pool::builder(manager)
+        .max_size(size)
+        .wait_timeout(Some(Duration::from_millis(500)))
+        .runtime(deadpool_diesel::Runtime::Tokio1)
+        .build()
+        .unwrap()
+}
+
+async fn truncate(db: &Pool) {
+    execute(db, "TRUNCATE logs, audit").await;
+}
+
+async fn execute(db: &Pool, sql: &'static str) {
+    let conn = db.get().await.unwrap();
+
+    conn.interact(move |conn| diesel::sql_query(sql).execute(conn))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+fn settings(values: &[(&str, &str)]) -> Settings {
+    let mut file = HashMap::from([("KEY".to_string(), TEST_KEY.to_string())]);
+
+    file.extend(
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string())),
+    );
+
+    Settings { file }
+}
+
+fn pg_state(db: &Arc<Pool>, values: &[(&str, &str)]) -> AppState {
+    let settings = settings(values);
+    let config = Config::from_env(&settings).unwrap();
+    let store = Arc::new(PgStore {
+        db: db.clone(),
+        query_timeout: config.query_timeout,
+        compress_threshold: config.compress_threshold,
+    });
+
+    app_state(store, settings, config)
+}
+
+fn app_state(store: Arc<dyn LogStore>, settings: Settings, config: Config) -> AppState {
+    AppState {
+        store,
+        read_only: AtomicBool::new(config.read_only),
+        config: RwLock::new(Arc::new(config)),
+        settings,
+        rate_limits: Mutex::new(HashMap::new()),
+        idempotency: Mutex::new(HashMap::new()),
+        live: broadcast::channel(LIVE_BUFFER).0,
+        shutdown: watch::channel(false).0,
+        metrics: Metrics::new().unwrap(),
+        start_time: Instant::now(),
+        webhook: None,
+    }
+}
+
+fn pg_app(test: &TestDb, values: &[(&str, &str)]) -> Router {
+    build_app(Arc::new(pg_state(&test.db, values)))
+}
+
+fn request(method: Method, uri: &str, body: Body) -> Request {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {TEST_KEY}"))
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(body)
+        .unwrap()
+}
+
+fn without_key(mut request: Request) -> Request {
+    request.headers_mut().remove(header::AUTHORIZATION);
+
+    request
+}
+
+fn with_key(mut request: Request, key: &str) -> Request {
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {key}")).unwrap(),
+    );
+
+    request
+}
+
+fn get_request(uri: &str) -> Request {
+    request(Method::GET, uri, Body::empty())
+}
+
+fn post_request(uri: &str, body: serde_json::Value) -> Request {
+    json_request(Method::POST, uri, body)
+}
+
+fn json_request(method: Method, uri: &str, body: serde_json::Value) -> Request {
+    let mut request = request(method, uri, Body::from(body.to_string()));
+
+    request.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    request
+}
+
+async fn send(app: &Router, request: Request) -> Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+async fn body_bytes(response: Response) -> Vec<u8> {
+    to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+async fn body_json(response: Response) -> serde_json::Value {
+    serde_json::from_slice(&body_bytes(response).await).unwrap()
+}
+
+async fn send_json(app: &Router, request: Request) -> (StatusCode, serde_json::Value) {
+    let response = send(app, request).await;
+
+    (response.status(), body_json(response).await)
+}
+
+async fn post_logs(app: &Router, logs: serde_json::Value) -> serde_json::Value {
+    let (status, body) = send_json(app, post_request("/v1/log/batch", logs)).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    body
+}
+
+fn minutes_ago(minutes: i64) -> String {
+    (chrono::Utc::now() - chrono::Duration::minutes(minutes)).to_rfc3339()
+}
+
+fn log_names(body: &serde_json::Value) -> Vec<&str> {
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| log["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn log_round_trips_through_giveme() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, stored) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "deploy", "data": { "version": 3 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["id"], stored["id"]);
+    assert_eq!(body["data"][0]["name"], "deploy");
+    assert_eq!(body["data"][0]["data"], json!({ "version": 3 }));
+}
+
+#[test]
+fn parse_limit_rejects_non_integer() {
+    assert_eq!(
+        parse_limit("abc"),
+        Err("Invalid LIMIT value \"abc\", expected integer >= 1".to_string())
+    );
+    assert_eq!(parse_limit("25"), Ok(25));
+}
+
+#[test]
+fn backoff_delay_doubles_up_to_the_cap() {
+    let delays = (1..=8).map(backoff_delay).collect::<Vec<_>>();
+
+    assert_eq!(
+        delays,
+        [500, 1000, 2000, 4000, 8000, 16_000, 30_000, 30_000].map(Duration::from_millis)
+    );
+    assert_eq!(backoff_delay(u32::MAX), CONNECT_BACKOFF_MAX);
+}
+
+#[test]
+fn bind_address_validates_host() {
+    assert_eq!(
+        bind_address("0.0.0.0", 8080),
+        Ok("0.0.0.0:8080".to_string())
+    );
+    assert_eq!(bind_address("::1", 8080), Ok("[::1]:8080".to_string()));
+    assert_eq!(
+        bind_address("localhost", 8080),
+        Ok("localhost:8080".to_string())
+    );
+    assert_eq!(
+        bind_address("local host", 8080),
+        Err("Invalid HOST value \"local host\", expected an IP address or hostname".to_string())
+    );
+    assert!(bind_address("", 8080).is_err());
+    assert!(bind_address("-db.internal", 8080).is_err());
+}
+
+#[test]
+fn config_reports_every_invalid_setting() {
+    let Err(ConfigError(errors)) = Config::from_env(&settings(&[
+        ("LIMIT", "abc"),
+        ("PORT", "99999"),
+        ("HOST", "local host"),
+        ("DB_POOL_SIZE", "0"),
+    ])) else {
+        panic!("invalid settings were accepted");
+    };
+
+    assert_eq!(errors.len(), 4, "{errors:?}");
+    assert!(errors.iter().any(|err| err.contains("LIMIT")));
+    assert!(errors.iter().any(|err| err.contains("PORT")));
+    assert!(errors.iter().any(|err| err.contains("HOST")));
+    assert!(errors.iter().any(|err| err.contains("DB_POOL_SIZE")));
+
+    let Err(ConfigError(errors)) = Config::from_env(&settings(&[
+        ("KEY", ""),
+        ("QUOTA_ACTION", "drop"),
+        ("RETENTION_DAYS", "-1"),
+    ])) else {
+        panic!("invalid settings were accepted");
+    };
+
+    assert_eq!(errors.len(), 3, "{errors:?}");
+}
+
+#[test]
+fn config_accepts_valid_settings() {
+    let config = Config::from_env(&settings(&[
+        ("HOST", "0.0.0.0"),
+        ("PORT", "9000"),
+        ("KEYS", "one,two"),
+        ("LIMIT", "50"),
+        ("DB_POOL_SIZE", "8"),
+        ("QUOTAS", "metrics:10"),
+        ("RETENTION_DAYS", "7"),
+    ]))
+    .unwrap();
+
+    assert_eq!(config.addr, "0.0.0.0:9000");
+    assert_eq!(config.keys.len(), 2);
+    assert_eq!(config.limit, 50);
+    assert_eq!(config.pool_size, 8);
+    assert_eq!(config.quotas["metrics"], 10);
+    assert_eq!(config.retention_days, Some(7));
+}
+
+#[tokio::test]
+async fn pool_size_setting_sizes_the_database_pool() {
+    let config = Config::from_env(&settings(&[("DB_POOL_SIZE", "7")])).unwrap();
+    let db = database_pool("postgres://postgres@127.0.0.1:1/logger", &config).unwrap();
+
+    assert_eq!(db.status().max_size, 7);
+
+    let config = Config::from_env(&settings(&[])).unwrap();
+    let db = database_pool("postgres://postgres@127.0.0.1:1/logger", &config).unwrap();
+
+    assert_eq!(db.status().max_size, DEFAULT_POOL_SIZE);
+}
+
+#[test]
+fn body_limits_reject_zero() {
+    assert!(Config::from_env(&settings(&[("MAX_BODY_BYTES", "0")])).is_err());
+    assert!(Config::from_env(&settings(&[("MAX_BATCH_BODY_BYTES", "0")])).is_err());
+    assert!(Config::from_env(&settings(&[("MAX_BODY_BYTES", "1")])).is_ok());
+}
+
+#[test]
+fn limit_rejects_zero_and_negative_and_clamps_to_max_all_rows() {
+    assert!(Config::from_env(&settings(&[("LIMIT", "0")])).is_err());
+    assert!(Config::from_env(&settings(&[("LIMIT", "-5")])).is_err());
+
+    let config = Config::from_env(&settings(&[("LIMIT", "500"), ("MAX_ALL_ROWS", "100")])).unwrap();
+
+    assert_eq!(config.limit, 100);
+
+    assert!(!valid_page(None, Some(0)));
+    assert!(!valid_page(None, Some(-1)));
+    assert!(!valid_page(Some(-1), None));
+    assert!(valid_page(Some(0), Some(1_000_000)));
+}
+
+#[tokio::test]
+async fn listener_receives_notified_log_ids() {
+    let Some(test) = test_db().await else { return };
+    let (notified, mut notifications) = tokio::sync::mpsc::channel(LIVE_BUFFER);
+    let listener = tokio::spawn(listen_for_logs(test.url.clone(), notified));
+    let log_id = Uuid::new_v4();
+    let conn = test.db.get().await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            conn.interact(move |conn| {
+                diesel::sql_query("SELECT pg_notify($1, $2)")
+                    .bind::<Text, _>(LIVE_CHANNEL)
+                    .bind::<Text, _>(log_id.to_string())
+                    .execute(conn)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+            if let Ok(Some(received)) =
+                tokio::time::timeout(Duration::from_millis(100), notifications.recv()).await
+            {
+                break received;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(received, log_id);
+
+    listener.abort();
+}
+
+#[test]
+fn webhook_url_accepts_http_and_https() {
+    assert!(parse_webhook_url("https://hooks.example.com/logs").is_ok());
+    assert!(parse_webhook_url("http://relay:8080/logs").is_ok());
+    assert!(parse_webhook_url("ftp://relay/logs").is_err());
+    assert!(parse_webhook_url("relay:8080").is_err());
+}
+
+#[tokio::test]
+async fn webhook_posts_signed_logs() {
+    let Some(test) = test_db().await else { return };
+    let (received, mut deliveries) = tokio::sync::mpsc::channel(1);
+    let hook = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: String| async move {
+            received.send((headers, body)).await.unwrap();
+            StatusCode::NO_CONTENT
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    tokio::spawn(async move { axum::serve(listener, hook).await.unwrap() });
+
+    let state = pg_state(
+        &test.db,
+        &[("WEBHOOK_URL", &url), ("WEBHOOK_SECRET", "hook-secret")],
+    );
+    let config = state.config();
+    let (queued, queue) = tokio::sync::mpsc::channel(WEBHOOK_BUFFER);
+
+    tokio::spawn(forward_webhooks(
+        config.webhook_url.clone().unwrap(),
+        config.webhook_secret.clone(),
+        queue,
+        state.shutdown.subscribe(),
+    ));
+
+    let app = build_app(Arc::new(AppState {
+        webhook: Some(queued),
+        ..state
+    }));
+    let (status, stored) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "deploy", "data": { "ok": true } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(5), deliveries.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    assert_eq!(
+        headers[header::HOST],
+        url.trim_start_matches("http://").trim_end_matches("/hook")
+    );
+    assert_eq!(
+        check_signature(
+            b"hook-secret",
+            DEFAULT_SIGNATURE_MAX_AGE,
+            &headers,
+            body.as_bytes()
+        ),
+        Ok(())
+    );
+    assert_eq!(payload["tenant"], DEFAULT_TENANT);
+    assert_eq!(payload["log"]["id"], stored["id"]);
+    assert_eq!(payload["log"]["data"], json!({ "ok": true }));
+}
+
+async fn walk_pages(app: &Router, page: &str) -> Vec<serde_json::Value> {
+    let mut ids = Vec::new();
+    let mut uri = page.to_string();
+
+    loop {
+        let (status, body) = send_json(app, get_request(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        ids.extend(
+            body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|log| log["id"].clone()),
+        );
+
+        match body["next_cursor"].as_str() {
+            Some(cursor) if body["meta"]["has_more"] == true => {
+                uri = format!("{page}&before={cursor}");
+            }
+            _ => return ids,
+        }
+    }
+}
+
+#[tokio::test]
+async fn cursor_pages_through_tied_timestamps() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let tied = (0..5)
+        .map(|n| json!({ "name": "tied", "data": { "n": n }, "created": "2024-01-01T00:00:00Z" }))
+        .collect::<Vec<_>>();
+
+    let (status, _) = send_json(&app, post_request("/v1/log", json!(tied))).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let ids = walk_pages(&app, "/v1/giveme?limit=2").await;
+    let unique = ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<std::collections::HashSet<_>>();
+
+    assert_eq!(ids.len(), 5);
+    assert_eq!(unique.len(), 5);
+
+    let (status, _) = send_json(
+        &app,
+        get_request(
+            "/v1/giveme?order=asc&before=2024-01-01T00:00:00Z,00000000-0000-0000-0000-000000000000",
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme?before=2024-01-01")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn data_filters_are_rejected_with_compression() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("COMPRESS_THRESHOLD_BYTES", "64")]);
+
+    let (status, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=user&data_value=alice"),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+
+    let (status, _) = send_json(&app, get_request("/v1/logs/search?q=alice")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme?name=deploy")).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn large_data_is_compressed_and_round_trips() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("COMPRESS_THRESHOLD_BYTES", "64")]);
+    let large = json!({ "lines": vec!["stack frame"; 100] });
+
+    for (name, data) in [("small", json!({ "ok": true })), ("large", large.clone())] {
+        let (status, _) = send_json(
+            &app,
+            post_request("/v1/log", json!({ "name": name, "data": data })),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let conn = test.db.get().await.unwrap();
+    let compressed = conn
+        .interact(|conn| {
+            logs::table
+                .select((logs::name, logs::data_compressed.is_not_null()))
+                .order(logs::name)
+                .load::<(String, bool)>(conn)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        compressed,
+        [("large".to_string(), true), ("small".to_string(), false)]
+    );
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?name=large")).await;
+
+    assert_eq!(body["data"][0]["data"], large);
+}
+
+#[tokio::test]
+async fn data_filter_uses_the_gin_index() {
+    let Some(test) = test_db().await else { return };
+
+    execute(
+        &test.db,
+        "INSERT INTO logs (id, name, data, created) SELECT gen_random_uuid(), 'bulk', jsonb_build_object('user', 'u' || n), now() FROM generate_series(1, 5000) n",
+    )
+    .await;
+    execute(&test.db, "ANALYZE logs").await;
+
+    let query = filter_logs(
+        DEFAULT_TENANT.to_string(),
+        LogQuery {
+            data: HashMap::from([("user".to_string(), "u7".to_string())]),
+            ..Default::default()
+        },
+    );
+    let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+    let (sql, _) = sql.split_once(" -- binds:").unwrap();
+    let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+
+    let plan = client
+        .simple_query(&format!(
+            "PREPARE filtered AS {sql}; EXPLAIN EXECUTE filtered('{DEFAULT_TENANT}', '{{\"user\": \"u7\"}}')"
+        ))
+        .await
+        .unwrap()
+        .into_iter()
+        .filter_map(|message| match message {
+            tokio_postgres::SimpleQueryMessage::Row(row) => row.get(0).map(str::to_string),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert!(plan.contains("logs_data_gin_idx"), "{plan}");
+}
+
+#[tokio::test]
+async fn data_filter_matches_strings_and_numbers() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let logs = json!([
+        { "name": "a", "data": { "user": "alice", "status": 500 } },
+        { "name": "b", "data": { "user": "bob", "status": "500" } },
+        { "name": "c", "data": { "user": "carol", "status": 200, "nested": { "user": "alice" } } },
+    ]);
+
+    send_json(&app, post_request("/v1/log", logs)).await;
+
+    let names = |body: serde_json::Value| {
+        let mut names = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|log| log["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        names.sort();
+        names
+    };
+
+    let (_, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=user&data_value=alice"),
+    )
+    .await;
+
+    assert_eq!(names(body), ["a"]);
+
+    let (_, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=status&data_value=500"),
+    )
+    .await;
+
+    assert_eq!(names(body), ["a", "b"]);
+}
+
+#[tokio::test]
+async fn data_filter_binds_keys_and_values() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "quoted", "data": { "note": "it's" } },
+            { "name": "odd-key", "data": { "o'key": "x" } },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=note&data_value=it%27s"),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(log_names(&body), ["quoted"]);
+
+    let (status, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=note&data_value=x%27%20OR%20%271%27%3D%271"),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(log_names(&body).is_empty(), "{body}");
+
+    let (_, body) = send_json(
+        &app,
+        get_request("/v1/giveme?data_key=o%27key&data_value=x"),
+    )
+    .await;
+
+    assert_eq!(log_names(&body), ["odd-key"]);
+}
+
+#[tokio::test]
+async fn count_rejects_invalid_query_as_json() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, body) = send_json(&app, get_request("/v1/count?name=a&name=b")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn every_error_carries_a_code() {
+    let errors = [
+        (ApiError::BadRequest("bad".into()), 400, "bad_request"),
+        (ApiError::InvalidJson("bad".into()), 400, "invalid_json"),
+        (ApiError::InvalidBody("bad".into()), 400, "invalid_body"),
+        (ApiError::Unauthorized("Unauthorized"), 401, "unauthorized"),
+        (ApiError::NotFound, 404, "not_found"),
+        (
+            ApiError::PayloadTooLarge("big".into()),
+            413,
+            "payload_too_large",
+        ),
+        (ApiError::Conflict("busy"), 409, "conflict"),
+        (ApiError::SchemaMismatch(Vec::new()), 422, "schema_mismatch"),
+        (
+            ApiError::RateLimited(Duration::from_secs(1)),
+            429,
+            "rate_limited",
+        ),
+        (
+            ApiError::QuotaExceeded("full".into()),
+            429,
+            "quota_exceeded",
+        ),
+        (ApiError::ReadOnly, 503, "read_only"),
+        (ApiError::DbUnavailable, 503, "db_unavailable"),
+        (ApiError::DbTimeout, 504, "db_timeout"),
+        (ApiError::Internal("broken"), 500, "internal"),
+    ];
+
+    for (error, status, code) in errors {
+        let response = error.into_response();
+
+        assert_eq!(response.status().as_u16(), status);
+
+        let body = body_json(response).await;
+
+        assert_eq!(body["status"], status);
+        assert_eq!(body["code"], code);
+        assert!(body["message"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn patched_logs_are_deduped_on_their_new_content() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (_, stored) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "a", "data": { "v": 1 } })),
+    )
+    .await;
+    let id = stored["id"].as_str().unwrap();
+
+    let (status, _) = send_json(
+        &app,
+        json_request(
+            Method::PATCH,
+            &format!("/v1/log/{id}"),
+            json!({ "data": { "v": 2 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log?dedupe=true",
+            json!({ "name": "a", "data": { "v": 2 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(body["deduped"], true);
+    assert_eq!(body["id"], id);
+
+    send_json(
+        &app,
+        json_request(
+            Method::PATCH,
+            &format!("/v1/log/{id}"),
+            json!({ "name": "b" }),
+        ),
+    )
+    .await;
+
+    let (_, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log?dedupe=true",
+            json!({ "name": "b", "data": { "v": 2 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(body["deduped"], true);
+    assert_eq!(body["id"], id);
+
+    let (_, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log?dedupe=true",
+            json!({ "name": "a", "data": { "v": 1 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(body["deduped"], false);
+}
+
+#[cfg(feature = "sqlite")]
+struct TestSqlite {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "sqlite")]
+impl Drop for TestSqlite {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+async fn sqlite_app(values: &[(&str, &str)]) -> (TestSqlite, Router) {
+    let test = TestSqlite {
+        path: env::temp_dir().join(format!("logger-rs-test-{}.db", Uuid::new_v4())),
+    };
+    let settings = settings(values);
+    let config = Config::from_env(&settings).unwrap();
+    let store = sqlite::SqliteStore::open(
+        test.path.to_str().unwrap(),
+        1,
+        config.query_timeout,
+        config.compress_threshold,
+        broadcast::channel(LIVE_BUFFER).0,
+    )
+    .await
+    .unwrap();
+    let app = build_app(Arc::new(app_state(Arc::new(store), settings, config)));
+
+    (test, app)
+}
+
+async fn assert_store_endpoints(app: &Router) {
+    let logs = json!([
+        { "name": "a", "data": { "n": 1 } },
+        { "name": "a", "data": { "n": 2 } },
+        { "name": "b", "data": { "n": 3 } },
+    ]);
+
+    let (status, stored) = send_json(app, post_request("/v1/log", logs)).await;
+
+    assert_eq!(status, StatusCode::OK, "{stored}");
+
+    let (_, body) = send_json(app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 3);
+
+    let (_, body) = send_json(app, get_request("/v1/count?name=a")).await;
+
+    assert_eq!(body["count"], 2);
+
+    let (_, body) = send_json(app, get_request("/v1/names")).await;
+
+    assert_eq!(body["names"], json!(["a", "b"]));
+
+    let id = stored["ids"][0].as_str().unwrap();
+    let (status, body) = send_json(
+        app,
+        json_request(
+            Method::PATCH,
+            &format!("/v1/log/{id}"),
+            json!({ "name": "c" }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "c");
+    assert_eq!(body["data"]["data"], json!({ "n": 1 }));
+
+    let (_, body) = send_json(app, get_request("/v1/names")).await;
+
+    assert_eq!(body["names"], json!(["a", "b", "c"]));
+
+    let (status, body) = send_json(
+        app,
+        request(Method::DELETE, "/v1/logs?name=a", Body::empty()),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deleted"], 1);
+
+    let (_, body) = send_json(
+        app,
+        request(Method::DELETE, "/v1/logs?name=b&hard=true", Body::empty()),
+    )
+    .await;
+
+    assert_eq!(body["deleted"], 1);
+
+    let (_, body) = send_json(app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+}
+
+async fn assert_store_reports(app: &Router) {
+    let old = minutes_ago(60 * 24 * 10);
+    let body = post_logs(
+        app,
+        json!([
+            { "name": "old", "data": 0, "created": old },
+            { "name": "a", "data": 1, "created": minutes_ago(90) },
+            { "name": "a", "data": 2, "created": minutes_ago(30) },
+            { "name": "b", "data": 3, "created": minutes_ago(10) },
+        ]),
+    )
+    .await;
+    let ids = body["ids"].as_array().unwrap().clone();
+
+    let (status, body) = send_json(app, get_request("/v1/latest")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(log_names(&body), ["a", "b", "old"]);
+    assert_eq!(body["data"][0]["data"], 2);
+
+    let (_, body) = send_json(app, get_request("/v1/latest?name=a")).await;
+
+    assert_eq!(log_names(&body), ["a"]);
+
+    let (status, body) = send_json(app, get_request("/v1/stats/by-name")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["counts"], json!({ "a": 2, "b": 1, "old": 1 }));
+
+    let (status, body) = send_json(app, get_request("/v1/stats/timeseries")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let buckets = body["data"].as_array().unwrap();
+
+    assert_eq!(
+        buckets
+            .iter()
+            .map(|b| b["count"].as_i64().unwrap())
+            .sum::<i64>(),
+        3
+    );
+    assert!(buckets.iter().all(|bucket| {
+        let bucket = bucket["bucket"].as_str().unwrap();
+
+        chrono::DateTime::parse_from_rfc3339(bucket)
+            .unwrap()
+            .timestamp()
+            % 3600
+            == 0
+    }));
+
+    let response = send(app, get_request("/v1/giveme?all=true&format=ndjson")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        String::from_utf8(body_bytes(response).await)
+            .unwrap()
+            .lines()
+            .count(),
+        4
+    );
+
+    let response = send(app, get_request("/v1/export.csv")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let csv = String::from_utf8(body_bytes(response).await).unwrap();
+
+    assert_eq!(csv.lines().count(), 5);
+    assert!(csv.lines().nth(1).unwrap().contains("\"old\""), "{csv}");
+
+    let (first, token) = export_page(app, "/v1/export?limit=3").await;
+    let token = token.unwrap();
+    let (rest, _) = export_page(app, &format!("/v1/export?after={token}")).await;
+
+    assert_eq!([first, rest].concat(), ids);
+
+    let (status, body) = send_json(
+        app,
+        request(Method::DELETE, "/v1/logs/retention?days=7", Body::empty()),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["deleted"], 1);
+
+    let (_, body) = send_json(app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 3);
+}
+
+#[tokio::test]
+async fn postgres_store_serves_count_names_patch_and_bulk_delete() {
+    let Some(test) = test_db().await else { return };
+
+    assert_store_endpoints(&pg_app(&test, &[])).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_store_serves_count_names_patch_and_bulk_delete() {
+    let (_test, app) = sqlite_app(&[]).await;
+
+    assert_store_endpoints(&app).await;
+}
+
+#[tokio::test]
+async fn postgres_store_serves_latest_stats_exports_and_retention() {
+    let Some(test) = test_db().await else { return };
+
+    assert_store_reports(&pg_app(&test, &[])).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_store_serves_latest_stats_exports_and_retention() {
+    let (_test, app) = sqlite_app(&[]).await;
+
+    assert_store_reports(&app).await;
+}
+
+#[tokio::test]
+async fn oversized_and_deep_data_report_the_limit() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let deep = format!(
+        "{{\"name\":\"deep\",\"data\":{}{}}}",
+        "[".repeat(MAX_DATA_DEPTH + 1),
+        "]".repeat(MAX_DATA_DEPTH + 1)
+    );
+    let mut request = request(Method::POST, "/v1/log", Body::from(deep));
+
+    request.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    let (status, body) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_body");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("data is nested deeper than 32 levels"),
+        "{body}"
+    );
+
+    let large = json!([{ "name": "large", "data": "x".repeat(MAX_DATA_BYTES) }]);
+    let (status, body) = send_json(&app, post_request("/v1/log/batch", large)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_body");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("[0].data: data is 1048578 bytes, larger than 1048576 bytes"),
+        "{body}"
+    );
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 0);
+}
+
+#[tokio::test]
+async fn all_rows_over_the_cap_are_rejected_before_loading() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("MAX_ALL_ROWS", "2")]);
+    let logs = json!([
+        { "name": "capped", "data": 1 },
+        { "name": "capped", "data": 2 },
+    ]);
+
+    let (status, _) = send_json(&app, post_request("/v1/log/batch", logs)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?all=true")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let (status, _) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "capped", "data": 3 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?all=true")).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["code"], "payload_too_large");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("More than 2 logs"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn read_only_toggle_blocks_writes_and_allows_reads() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let log = json!({ "name": "frozen", "data": 1 });
+
+    let (status, _) = send_json(&app, post_request("/v1/log", log.clone())).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let toggle = post_request("/v1/admin/readonly", json!({ "read_only": true }));
+    let (status, body) = send_json(&app, toggle).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["read_only"], true);
+
+    let response = send(&app, post_request("/v1/log", log.clone())).await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!response.headers().contains_key(header::RETRY_AFTER));
+    assert_eq!(body_json(response).await["code"], "read_only");
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+    let toggle = post_request("/v1/admin/readonly", json!({ "read_only": false }));
+    let (status, _) = send_json(&app, toggle).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send_json(&app, post_request("/v1/log", log)).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn retention_sweep_is_skipped_while_read_only() {
+    let Some(test) = test_db().await else { return };
+    let state = Arc::new(pg_state(&test.db, &[("READ_ONLY", "true")]));
+    let app = build_app(state.clone());
+
+    execute(
+        &test.db,
+        "INSERT INTO logs (id, name, data, created, hash) \
+         VALUES (gen_random_uuid(), 'old', '1', now() - interval '30 days', 'old')",
+    )
+    .await;
+
+    let sweeps = tokio::spawn(retention_sweeps(
+        state.clone(),
+        7,
+        Duration::from_millis(20),
+    ));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+
+    state.read_only.store(false, Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    sweeps.abort();
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 0);
+}
+
+#[tokio::test]
+async fn cors_allows_configured_origins() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("CORS_ORIGINS", "https://dashboard.example.com")]);
+    let mut request = get_request("/v1/count");
+
+    request.headers_mut().insert(
+        header::ORIGIN,
+        HeaderValue::from_static("https://dashboard.example.com"),
+    );
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://dashboard.example.com"
+    );
+
+    let mut request = get_request("/v1/count");
+
+    request.headers_mut().insert(
+        header::ORIGIN,
+        HeaderValue::from_static("https://evil.example.com"),
+    );
+
+    let response = send(&app, request).await;
+
+    assert!(!response
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn cors_preflight_returns_no_content() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("CORS_ORIGINS", "https://dashboard.example.com")]);
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/v1/log")
+        .header(header::ORIGIN, "https://dashboard.example.com")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://dashboard.example.com"
+    );
+    assert!(response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS]
+        .to_str()
+        .unwrap()
+        .contains("POST"));
+}
+
+#[tokio::test]
+async fn log_and_giveme_set_the_response_status() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let response = send(
+        &app,
+        post_request("/v1/log", json!({ "name": "ok", "data": 1 })),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, post_request("/v1/log", json!({ "data": 1 }))).await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut request = get_request("/v1/giveme");
+
+    request.headers_mut().remove(header::AUTHORIZATION);
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(&app, get_request("/v1/giveme?limit=abc")).await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn saturated_pool_returns_unavailable() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 1));
+    let app = build_app(Arc::new(pg_state(&db, &[])));
+    let _held = db.get().await.unwrap();
+
+    let (status, body) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "busy", "data": 1 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "db_unavailable");
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "db_unavailable");
+}
+
+#[tokio::test]
+async fn giveme_returns_the_second_page() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let logs: Vec<_> = (1..=5)
+        .map(|n| json!({ "name": format!("log{n}"), "data": n, "created": minutes_ago(10 - n) }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?offset=2&limit=2")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log_names(&body), ["log3", "log2"]);
+}
+
+#[tokio::test]
+async fn giveme_filters_by_name() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "deploy", "data": 1 },
+            { "name": "error", "data": 2 },
+            { "name": "deploy", "data": 3 },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?name=deploy")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log_names(&body), ["deploy", "deploy"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?name=missing")).await;
+
+    assert!(log_names(&body).is_empty());
+}
+
+#[tokio::test]
+async fn giveme_created_range_is_inclusive() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let at = |minutes: i64| {
+        (chrono::Utc::now()
+            .duration_trunc(chrono::Duration::hours(1))
+            .unwrap()
+            - chrono::Duration::minutes(minutes))
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    };
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "before", "data": 1, "created": at(30) },
+            { "name": "first", "data": 2, "created": at(20) },
+            { "name": "last", "data": 3, "created": at(10) },
+            { "name": "after", "data": 4, "created": at(0) },
+        ]),
+    )
+    .await;
+
+    let uri = format!(
+        "/v1/giveme?created_after={}&created_before={}",
+        at(20),
+        at(10)
+    );
+    let (status, body) = send_json(&app, get_request(&uri)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log_names(&body), ["last", "first"]);
+}
+
+#[tokio::test]
+async fn deleted_log_is_not_found() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let (_, stored) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "gone", "data": 1 })),
+    )
+    .await;
+    let uri = format!("/v1/log/{}", stored["id"].as_str().unwrap());
+
+    let (status, _) = send_json(&app, get_request(&uri)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send_json(&app, request(Method::DELETE, &uri, Body::empty())).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(&app, get_request(&uri)).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+
+    let mut unauthenticated = request(Method::DELETE, &uri, Body::empty());
+
+    unauthenticated.headers_mut().remove(header::AUTHORIZATION);
+
+    let (status, _) = send_json(&app, unauthenticated).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn batch_inserts_every_log_and_rejects_oversized_batches() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let body = post_logs(
+        &app,
+        json!([
+            { "name": "a", "data": 1 },
+            { "name": "b", "data": 2 },
+            { "name": "c", "data": 3 },
+        ]),
+    )
+    .await;
+
+    assert_eq!(body["inserted"], 3);
+    assert_eq!(body["ids"].as_array().unwrap().len(), 3);
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 3);
+
+    let oversized: Vec<_> = (0..=MAX_BATCH_SIZE)
+        .map(|n| json!({ "name": "many", "data": n }))
+        .collect();
+    let (status, body) = send_json(&app, post_request("/v1/log/batch", json!(oversized))).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["code"], "payload_too_large");
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 3);
+}
+
+#[tokio::test]
+async fn count_returns_the_number_of_matching_logs() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "deploy", "data": 1 },
+            { "name": "deploy", "data": 2 },
+            { "name": "error", "data": 3 },
+            { "name": "error", "data": 4 },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 4);
+
+    let (status, body) = send_json(&app, get_request("/v1/count?name=error")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 2);
+}
+
+#[tokio::test]
+async fn app_state_uses_the_injected_key() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("KEY", "injected")]);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let mut request = get_request("/v1/giveme");
+
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_static("Bearer injected"),
+    );
+
+    let (status, _) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn key_is_accepted_as_bearer_header_or_query_param() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/v1/giveme?key={TEST_KEY}");
+    let (status, _) = send_json(&app, without_key(get_request(&uri))).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let mut request = get_request("/v1/giveme");
+
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_static("Bearer wrong"),
+    );
+
+    let (status, body) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+
+    let request = without_key(get_request("/v1/giveme?key=wrong"));
+    let (status, _) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send_json(&app, without_key(get_request("/v1/giveme"))).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unauthenticated_requests_never_reach_the_handler() {
+    let Some(test) = test_db().await else { return };
+    let state = Arc::new(pg_state(&test.db, &[]));
+    let app = build_app(state.clone());
+
+    let (status, _) = send_json(&app, without_key(get_request("/v1/giveme"))).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(state.metrics.giveme_requests.get(), 0);
+    assert_eq!(state.metrics.auth_failures.get(), 1);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state.metrics.giveme_requests.get(), 1);
+}
+
+#[tokio::test]
+async fn any_listed_key_authorizes() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("KEYS", "first, second")]);
+
+    for key in ["first", "second"] {
+        let uri = format!("/v1/giveme?key={key}");
+        let (status, _) = send_json(&app, without_key(get_request(&uri))).await;
+
+        assert_eq!(status, StatusCode::OK, "{key}");
+    }
+
+    for key in ["third", TEST_KEY] {
+        let uri = format!("/v1/giveme?key={key}");
+        let (status, _) = send_json(&app, without_key(get_request(&uri))).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{key}");
+    }
+}
+
+#[tokio::test]
+async fn rate_limit_rejects_requests_past_the_limit() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("RATE_LIMIT_PER_MIN", "3")]);
+
+    for n in 0..3 {
+        let (status, _) = send_json(
+            &app,
+            post_request("/v1/log", json!({ "name": "burst", "data": n })),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let response = send(
+        &app,
+        post_request("/v1/log", json!({ "name": "burst", "data": 3 })),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+    assert_eq!(body_json(response).await["code"], "rate_limited");
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn in_flight_request_completes_during_graceful_shutdown() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 1));
+    let state = pg_state(&db, &[]);
+    let (queued, queue) = tokio::sync::mpsc::channel(WEBHOOK_BUFFER);
+    let webhook = tokio::spawn(forward_webhooks(
+        parse_webhook_url("http://127.0.0.1:9/hook").unwrap(),
+        None,
+        queue,
+        state.shutdown.subscribe(),
+    ));
+    let state = Arc::new(AppState {
+        webhook: Some(queued),
+        ..state
+    });
+    let sweeps = tokio::spawn(retention_sweeps(
+        state.clone(),
+        7,
+        Duration::from_secs(3600),
+    ));
+    let app = build_app(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn({
+        let state = state.clone();
+
+        async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal(state))
+            .await
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let held = db.get().await.unwrap();
+    let in_flight = tokio::spawn(
+        reqwest::Client::new()
+            .post(format!("http://{addr}/v1/log"))
+            .bearer_auth(TEST_KEY)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "name": "draining", "data": 1 }).to_string())
+            .send(),
+    );
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    state.shutdown.send_replace(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop(held);
+
+    let response = in_flight.await.unwrap().unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    server.await.unwrap().unwrap();
+
+    for task in [webhook, sweeps] {
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let (_, body) = send_json(&pg_app(&test, &[]), get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+}
+
+#[tokio::test]
+async fn health_reports_ok_and_degraded_when_the_pool_is_exhausted() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 1));
+    let app = build_app(Arc::new(pg_state(&db, &[])));
+
+    let (status, body) = send_json(&app, without_key(get_request("/health"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+
+    let _held = db.get().await.unwrap();
+    let (status, body) = send_json(&app, without_key(get_request("/health"))).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "degraded");
+}
+
+#[tokio::test]
+async fn health_detail_reports_pool_statistics() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 3));
+    let app = build_app(Arc::new(pg_state(&db, &[])));
+
+    let (status, body) = send_json(&app, get_request("/v1/health/detail")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+
+    for field in ["size", "available", "max_size"] {
+        assert!(body["pool"][field].is_u64(), "{field}: {body}");
+    }
+
+    assert_eq!(body["pool"]["max_size"], 3);
+}
+
+#[tokio::test]
+async fn returned_id_fetches_the_stored_log() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, stored) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "fetch", "data": { "ok": true } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let id = stored["id"].as_str().unwrap();
+    let (status, body) = send_json(&app, get_request(&format!("/v1/log/{id}"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], id);
+    assert_eq!(body["data"]["name"], "fetch");
+    assert_eq!(body["data"]["data"], json!({ "ok": true }));
+
+    let missing = Uuid::new_v4();
+    let (status, _) = send_json(&app, get_request(&format!("/v1/log/{missing}"))).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn fetching_a_log_checks_the_id_and_the_key() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, body) = send_json(&app, get_request("/v1/log/not-a-uuid")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["code"], "bad_request");
+
+    let missing = format!("/v1/log/{}", Uuid::new_v4());
+    let (status, body) = send_json(&app, get_request(&missing)).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["message"], "Not found");
+
+    let (status, _) = send_json(&app, without_key(get_request(&missing))).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn giveme_sorts_in_both_orders() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "middle", "data": 2, "created": minutes_ago(20) },
+            { "name": "newest", "data": 3, "created": minutes_ago(10) },
+            { "name": "oldest", "data": 1, "created": minutes_ago(30) },
+        ]),
+    )
+    .await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?order=asc")).await;
+
+    assert_eq!(log_names(&body), ["oldest", "middle", "newest"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?order=desc")).await;
+
+    assert_eq!(log_names(&body), ["newest", "middle", "oldest"]);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme?order=sideways")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn ndjson_has_one_line_per_log() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let logs: Vec<_> = (0..5)
+        .map(|n| json!({ "name": "line", "data": n }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let response = send(&app, get_request("/v1/giveme?all=true&format=ndjson")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+
+    let body = String::from_utf8(body_bytes(response).await).unwrap();
+    let lines: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 5);
+    assert!(lines.iter().all(|log| log["name"] == "line"));
+}
+
+#[tokio::test]
+async fn csv_export_has_a_header_and_quoted_rows() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let created = "2024-03-01T12:00:00Z";
+    let body = post_logs(
+        &app,
+        json!([{ "name": "say \"hi\", twice", "data": { "a": "b" }, "created": created }]),
+    )
+    .await;
+    let id = body["ids"][0].as_str().unwrap();
+
+    let response = send(&app, get_request("/v1/export.csv")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+
+    let csv = String::from_utf8(body_bytes(response).await).unwrap();
+    let mut rows = csv.lines();
+
+    assert_eq!(rows.next(), Some("id,name,data,created"));
+    assert_eq!(
+        rows.next().unwrap(),
+        format!("{id},\"say \"\"hi\"\", twice\",\"{{\"\"a\"\":\"\"b\"\"}}\",{created}")
+    );
+    assert_eq!(rows.next(), None);
+}
+
+#[tokio::test]
+async fn logs_are_stored_and_filtered_by_level() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "crash", "data": 1, "level": "error" },
+            { "name": "boot", "data": 2 },
+        ]),
+    )
+    .await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?level=error")).await;
+
+    assert_eq!(log_names(&body), ["crash"]);
+    assert_eq!(body["data"][0]["level"], "error");
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?level=info")).await;
+
+    assert_eq!(log_names(&body), ["boot"]);
+
+    let (status, _) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "bad", "data": 3, "level": "loud" }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn retention_deletes_only_logs_older_than_the_cutoff() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    execute(
+        &test.db,
+        "INSERT INTO logs (id, name, data, created, hash) \
+         VALUES (gen_random_uuid(), 'old', '1', now() - interval '30 days', 'old')",
+    )
+    .await;
+    post_logs(&app, json!([{ "name": "recent", "data": 2 }])).await;
+
+    let sweep = request(Method::DELETE, "/v1/logs/retention?days=7", Body::empty());
+    let (status, body) = send_json(&app, sweep).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deleted"], 1);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body), ["recent"]);
+
+    let invalid = request(Method::DELETE, "/v1/logs/retention?days=0", Body::empty());
+    let (status, _) = send_json(&app, invalid).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn retention_sweep_deletes_backdated_logs_on_each_tick() {
+    let Some(test) = test_db().await else { return };
+    let state = Arc::new(pg_state(&test.db, &[]));
+    let app = build_app(state.clone());
+
+    post_logs(&app, json!([{ "name": "recent", "data": 1 }])).await;
+    execute(
+        &test.db,
+        "INSERT INTO logs (id, name, data, created, hash) \
+         VALUES (gen_random_uuid(), 'old', '1', now() - interval '30 days', 'old')",
+    )
+    .await;
+
+    let sweeps = tokio::spawn(retention_sweeps(
+        state.clone(),
+        7,
+        Duration::from_millis(20),
+    ));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    sweeps.abort();
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body), ["recent"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/audit")).await;
+
+    assert_eq!(body["data"][0]["operation"], "retention_sweep");
+    assert_eq!(body["data"][0]["affected"], 1);
+}
+
+#[test]
+fn tracing_init_does_not_panic() {
+    init_tracing(&settings(&[("RUST_LOG", "off")]));
+
+    info!("tracing initialised");
+}
+
+#[test]
+fn log_filter_honors_standard_directives() {
+    let (filter, invalid) = log_filter(Some("warn,logger_rs=trace"));
+
+    assert!(invalid.is_none());
+    assert_eq!(filter.to_string(), "logger_rs=trace,warn");
+
+    let (filter, invalid) = log_filter(Some("info"));
+
+    assert!(invalid.is_none());
+    assert_eq!(filter.to_string(), "info");
+
+    let (filter, invalid) = log_filter(Some("logger_rs=loud"));
+
+    assert!(invalid.unwrap().contains("Invalid RUST_LOG value"));
+    assert_eq!(filter.to_string(), DEFAULT_LOG_FILTER);
+
+    let (filter, invalid) = log_filter(None);
+
+    assert!(invalid.is_none());
+    assert_eq!(filter.to_string(), DEFAULT_LOG_FILTER);
+}
+
+#[tokio::test]
+async fn metrics_count_ingested_logs() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(&app, json!([{ "name": "counted", "data": 1 }])).await;
+
+    let response = send(&app, without_key(get_request("/metrics"))).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let metrics = String::from_utf8(body_bytes(response).await).unwrap();
+
+    assert!(
+        metrics.lines().any(|line| line == "logs_ingested_total 1"),
+        "{metrics}"
+    );
+}
+
+#[tokio::test]
+async fn oversized_body_is_rejected_before_the_database() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 1));
+    let app = build_app(Arc::new(pg_state(&db, &[("MAX_BODY_BYTES", "64")])));
+    let _held = db.get().await.unwrap();
+    let log = json!({ "name": "large", "data": "x".repeat(64) });
+
+    let (status, body) = send_json(&app, post_request("/v1/log", log)).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["code"], "payload_too_large");
+}
+
+#[tokio::test]
+async fn unknown_routes_return_json_not_found() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let response = send(&app, get_request("/v1/nothing/here")).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    assert_eq!(
+        body_json(response).await,
+        json!({ "status": 404, "code": "not_found", "message": "Not found" })
+    );
+}
+
+#[tokio::test]
+async fn log_names_must_be_non_empty_and_bounded() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("MAX_NAME_LENGTH", "8")]);
+
+    for name in [String::new(), "x".repeat(9)] {
+        let (status, body) = send_json(
+            &app,
+            post_request("/v1/log", json!({ "name": name, "data": 1 })),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{name}");
+        assert_eq!(body["code"], "bad_request");
+    }
+
+    let (status, _) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "x".repeat(8), "data": 1 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+struct FreshDb {
+    client: tokio_postgres::Client,
+    name: String,
+    db: Pool,
+}
+
+impl FreshDb {
+    async fn create(test: &TestDb) -> FreshDb {
+        let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+        tokio::spawn(connection);
+
+        let name = format!("logger_fresh_{}", Uuid::new_v4().simple());
+        let (base, _) = test.url.rsplit_once('/').unwrap();
+
+        client
+            .batch_execute(&format!("CREATE DATABASE {name}"))
+            .await
+            .unwrap();
+
+        let db = test_pool(&format!("{base}/{name}"), 1);
+
+        FreshDb { client, name, db }
+    }
+
+    async fn drop(self) {
+        self.db.close();
+        self.client
+            .batch_execute(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn migrations_create_the_schema_on_a_fresh_database() {
+    let Some(test) = test_db().await else { return };
+    let fresh = FreshDb::create(&test).await;
+
+    let applied = run_migrations(&fresh.db, DEFAULT_QUERY_TIMEOUT).await;
+    let schema = check_schema(&fresh.db, DEFAULT_QUERY_TIMEOUT).await;
+    let again = run_migrations(&fresh.db, DEFAULT_QUERY_TIMEOUT).await;
+
+    fresh.drop().await;
+
+    assert!(applied.unwrap() > 0);
+    assert_eq!(schema, Ok(()));
+    assert_eq!(again, Ok(0));
+}
+
+#[tokio::test]
+async fn slow_queries_time_out_with_gateway_timeout() {
+    let Some(test) = test_db().await else { return };
+    let conn = test.db.get().await.unwrap();
+
+    let slow = interact(conn, Duration::from_millis(100), |conn| {
+        diesel::sql_query("SELECT pg_sleep(1)").execute(conn)
+    })
+    .await;
+
+    let Err(err) = slow else {
+        panic!("pg_sleep finished before the timeout");
+    };
+
+    assert!(matches!(err, QueryError::Timeout));
+
+    let response = query_error(err, "Could not get data").into_response();
+
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body_json(response).await["code"], "db_timeout");
+
+    let app = pg_app(&test, &[("DB_QUERY_TIMEOUT_MS", "200")]);
+    let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+    client
+        .batch_execute("BEGIN; LOCK TABLE logs IN ACCESS EXCLUSIVE MODE")
+        .await
+        .unwrap();
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    client.batch_execute("ROLLBACK").await.unwrap();
+
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["code"], "db_timeout");
+}
+
+#[tokio::test]
+async fn timed_out_queries_are_cancelled_on_the_server() {
+    let Some(test) = test_db().await else { return };
+    let manager = Manager::from_config(
+        test.url.clone(),
+        deadpool_diesel::Runtime::Tokio1,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Verified,
+        },
+    );
+    let db = Pool::builder(manager)
+        .max_size(1)
+        .post_create(statement_timeout(Duration::from_millis(300)))
+        .runtime(deadpool_diesel::Runtime::Tokio1)
+        .build()
+        .unwrap();
+
+    let slow = interact(
+        db.get().await.unwrap(),
+        Duration::from_millis(100),
+        |conn| diesel::sql_query("SELECT pg_sleep(5)").execute(conn),
+    )
+    .await;
+
+    assert!(matches!(slow, Err(QueryError::Timeout)));
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+
+    let running: i64 = client
+        .query_one(
+            "SELECT count(*) FROM pg_stat_activity WHERE state = 'active' AND query = 'SELECT pg_sleep(5)'",
+            &[],
+        )
+        .await
+        .unwrap()
+        .get(0);
+
+    assert_eq!(running, 0);
+
+    let conn = db.get().await.unwrap();
+    let setting = conn
+        .interact(|conn| {
+            diesel::sql_query("SELECT current_setting('statement_timeout') AS setting")
+                .get_result::<Setting>(conn)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(setting.setting, "300ms");
+}
+
+#[derive(QueryableByName)]
+struct Setting {
+    #[diesel(sql_type = Text)]
+    setting: String,
+}
+
+#[tokio::test]
+async fn query_combines_name_date_range_and_limit() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "deploy", "data": 1, "created": minutes_ago(50) },
+            { "name": "deploy", "data": 2, "created": minutes_ago(30) },
+            { "name": "deploy", "data": 3, "created": minutes_ago(25) },
+            { "name": "deploy", "data": 4, "created": minutes_ago(20) },
+            { "name": "error", "data": 5, "created": minutes_ago(25) },
+        ]),
+    )
+    .await;
+
+    let filter = json!({
+        "name": "deploy",
+        "created_after": minutes_ago(40),
+        "created_before": minutes_ago(15),
+        "limit": 2,
+    });
+    let (status, body) = send_json(&app, post_request("/v1/query", filter)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let data: Vec<_> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| log["data"].clone())
+        .collect();
+
+    assert_eq!(data, [json!(4), json!(3)]);
+}
+
+#[tokio::test]
+async fn names_are_deduplicated() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "worker", "data": 1 },
+            { "name": "api", "data": 2 },
+            { "name": "worker", "data": 3 },
+            { "name": "cron", "data": 4 },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/names")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["names"], json!(["api", "cron", "worker"]));
+}
+
+#[tokio::test]
+async fn stats_count_logs_per_name() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "deploy", "data": 1 },
+            { "name": "error", "data": 2 },
+            { "name": "deploy", "data": 3 },
+            { "name": "deploy", "data": 4, "created": minutes_ago(120) },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/stats/by-name")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["counts"], json!({ "deploy": 3, "error": 1 }));
+
+    let uri = format!(
+        "/v1/stats/by-name?created_after={}",
+        minutes_ago(60).replace('+', "%2B")
+    );
+    let (_, body) = send_json(&app, get_request(&uri)).await;
+
+    assert_eq!(body["counts"], json!({ "deploy": 2, "error": 1 }));
+}
+
+#[tokio::test]
+async fn timeseries_buckets_logs_by_hour() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let hour = chrono::Utc::now()
+        .duration_trunc(chrono::Duration::hours(1))
+        .unwrap();
+    let at = |minutes: i64| (hour - chrono::Duration::minutes(minutes)).to_rfc3339();
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "tick", "data": 1, "created": at(90) },
+            { "name": "tick", "data": 2, "created": at(80) },
+            { "name": "tick", "data": 3, "created": at(30) },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/stats/timeseries?bucket=hour")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["data"],
+        json!([
+            { "bucket": hour - chrono::Duration::hours(2), "count": 2 },
+            { "bucket": hour - chrono::Duration::hours(1), "count": 1 },
+        ])
+    );
+
+    let (status, _) = send_json(&app, get_request("/v1/stats/timeseries?bucket=week")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn nested_data_round_trips_byte_for_byte() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let data = r#"{"a":[1,2.5,{"b":null}],"cc":{"d":[true,"x"],"ee":[]},"fff":"é"}"#;
+    let body = format!("{{\"name\":\"nested\",\"data\":{data}}}");
+    let mut request = request(Method::POST, "/v1/log", Body::from(body));
+
+    request.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    let (status, stored) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/v1/log/{}/data", stored["id"].as_str().unwrap());
+    let response = send(&app, get_request(&uri)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_bytes(response).await, data.as_bytes());
+}
+
+#[tokio::test]
+async fn repeated_idempotency_key_stores_one_log() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let keyed = |key: &'static str, data: i64| {
+        let mut request = post_request("/v1/log", json!({ "name": "charge", "data": data }));
+
+        request.headers_mut().insert(
+            HeaderName::from_static(IDEMPOTENCY_KEY),
+            HeaderValue::from_static(key),
+        );
+
+        request
+    };
+
+    let (status, first) = send_json(&app, keyed("charge-1", 1)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, second) = send_json(&app, keyed("charge-1", 2)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["id"], second["id"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+
+    let (_, third) = send_json(&app, keyed("charge-2", 2)).await;
+
+    assert_ne!(first["id"], third["id"]);
+}
+
+fn with_idempotency_key(mut request: Request, key: &'static str) -> Request {
+    request.headers_mut().insert(
+        HeaderName::from_static(IDEMPOTENCY_KEY),
+        HeaderValue::from_static(key),
+    );
+
+    request
+}
+
+#[tokio::test]
+async fn concurrent_idempotency_keys_store_one_log() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let writes = (0..8)
+        .map(|n| {
+            let app = app.clone();
+            let request = post_request("/v1/log", json!({ "name": "charge", "data": n }));
+
+            tokio::spawn(
+                async move { send_json(&app, with_idempotency_key(request, "charge-1")).await },
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut ids = Vec::new();
+
+    for write in writes {
+        let (status, body) = write.await.unwrap();
+
+        match status {
+            StatusCode::OK => ids.push(body["id"].clone()),
+            StatusCode::CONFLICT => assert_eq!(body["code"], "conflict"),
+            _ => panic!("{status} {body}"),
+        }
+    }
+
+    ids.dedup();
+
+    assert_eq!(ids.len(), 1, "{ids:?}");
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+}
+
+#[tokio::test]
+async fn pending_idempotency_keys_conflict_and_failures_release_them() {
+    let Some(test) = test_db().await else { return };
+    let state = Arc::new(pg_state(&test.db, &[("QUOTAS", "charge:1")]));
+    let app = build_app(state.clone());
+
+    state
+        .idempotency
+        .lock()
+        .unwrap()
+        .insert(format!("{DEFAULT_TENANT}:busy"), (Instant::now(), None));
+
+    let request = post_request("/v1/log", json!({ "name": "charge", "data": 1 }));
+    let (status, body) = send_json(&app, with_idempotency_key(request, "busy")).await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{body}");
+
+    let batch = post_request(
+        "/v1/log/batch",
+        json!([{ "name": "charge", "data": 1 }, { "name": "charge", "data": 2 }]),
+    );
+    let (status, _) = send_json(&app, with_idempotency_key(batch, "retry")).await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let request = post_request("/v1/log", json!({ "name": "charge", "data": 1 }));
+    let (status, body) = send_json(&app, with_idempotency_key(request, "retry")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body["id"].is_string());
+}
+
+#[tokio::test]
+async fn giveme_returns_an_envelope_or_a_bare_array() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(&app, json!([{ "name": "shape", "data": 1 }])).await;
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], 200);
+    assert_eq!(body["message"], "OK");
+    assert_eq!(log_names(&body), ["shape"]);
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?envelope=false")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["name"], "shape");
+}
+
+#[tokio::test]
+async fn has_more_is_set_on_full_pages_only() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let logs: Vec<_> = (1..=3)
+        .map(|n| json!({ "name": "paged", "data": n, "created": minutes_ago(10 - n) }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?limit=2")).await;
+
+    assert_eq!(body["meta"]["count"], 2);
+    assert_eq!(body["meta"]["has_more"], true);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?limit=2&offset=2")).await;
+
+    assert_eq!(body["meta"]["count"], 1);
+    assert_eq!(body["meta"]["has_more"], false);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?limit=3")).await;
+
+    assert_eq!(body["meta"]["has_more"], false);
+}
+
+#[tokio::test]
+async fn patch_updates_data_or_name_and_rejects_missing_ids() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let (_, stored) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "draft", "data": { "v": 1 } })),
+    )
+    .await;
+    let uri = format!("/v1/log/{}", stored["id"].as_str().unwrap());
+
+    let (status, body) = send_json(
+        &app,
+        json_request(Method::PATCH, &uri, json!({ "data": { "v": 2 } })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "draft");
+    assert_eq!(body["data"]["data"], json!({ "v": 2 }));
+    assert!(body["data"]["updated"].is_string());
+
+    let (status, body) = send_json(
+        &app,
+        json_request(Method::PATCH, &uri, json!({ "name": "final" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "final");
+    assert_eq!(body["data"]["data"], json!({ "v": 2 }));
+
+    let (_, body) = send_json(&app, get_request(&uri)).await;
+
+    assert_eq!(body["data"]["name"], "final");
+
+    let missing = format!("/v1/log/{}", Uuid::new_v4());
+    let (status, body) = send_json(
+        &app,
+        json_request(Method::PATCH, &missing, json!({ "name": "x" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn stream_delivers_logs_posted_after_subscribing() {
+    let Some(test) = test_db().await else { return };
+    let state = Arc::new(pg_state(&test.db, &[]));
+    let app = build_app(state.clone());
+    let (notified, notifications) = tokio::sync::mpsc::channel(LIVE_BUFFER);
+    let listener = tokio::spawn(listen_for_logs(test.url.clone(), notified));
+    let republisher = tokio::spawn(republish_logs(
+        state.clone(),
+        test.db.clone(),
+        notifications,
+    ));
+
+    let response = send(&app, get_request("/v1/stream")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+
+    let mut events = response.into_body().into_data_stream();
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        let mut sent = 0;
+
+        loop {
+            sent += 1;
+            post_logs(&app, json!([{ "name": "live", "data": sent }])).await;
+
+            let next = tokio::time::timeout(Duration::from_millis(200), events.next()).await;
+
+            if let Ok(Some(Ok(event))) = next {
+                return String::from_utf8(event.to_vec()).unwrap();
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    listener.abort();
+    republisher.abort();
+
+    assert!(received.starts_with("event: log\n"), "{received}");
+    assert!(received.contains("\"name\":\"live\""), "{received}");
+}
+
+#[tokio::test]
+async fn large_responses_are_gzipped_when_requested() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let logs: Vec<_> = (0..50)
+        .map(
+            |n| json!({ "name": "verbose", "data": { "n": n, "text": "lorem ipsum ".repeat(20) } }),
+        )
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let plain = body_json(send(&app, get_request("/v1/giveme?envelope=false")).await).await;
+    let mut request = get_request("/v1/giveme?envelope=false");
+
+    request
+        .headers_mut()
+        .insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+    let compressed = body_bytes(response).await;
+    let decompressed: serde_json::Value =
+        serde_json::from_reader(GzDecoder::new(compressed.as_slice())).unwrap();
+
+    assert_eq!(decompressed, plain);
+    assert_eq!(decompressed.as_array().unwrap().len(), 50);
+}
+
+#[tokio::test]
+async fn tls_loads_a_self_signed_pem_pair() {
+    let fixture = |file: &str| format!("{}/tests/fixtures/{file}", env!("CARGO_MANIFEST_DIR"));
+
+    assert!(load_tls(&fixture("cert.pem"), &fixture("key.pem"))
+        .await
+        .is_ok());
+
+    let err = load_tls(&fixture("key.pem"), &fixture("key.pem"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err, "expected a PEM encoded certificate");
+    assert!(load_tls(&fixture("missing.pem"), &fixture("key.pem"))
+        .await
+        .is_err());
+}
+
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn requests_are_logged_with_status_and_latency() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let captured = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer({
+            let captured = captured.clone();
+            move || captured.clone()
+        })
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    send(&app, get_request("/v1/count")).await;
+
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let line = logs
+        .lines()
+        .find(|line| line.contains("finished request"))
+        .unwrap_or_else(|| panic!("{logs}"));
+
+    assert!(line.contains("status=200"), "{line}");
+    assert!(line.contains("latency_ms="), "{line}");
+    assert!(line.contains("path=/v1/count"), "{line}");
+}
+
+#[tokio::test]
+async fn data_is_validated_against_the_schema_for_its_name() {
+    let Some(test) = test_db().await else { return };
+    let dir = env::temp_dir().join(format!("logger-schemas-{}", Uuid::new_v4()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("deploy.json"),
+        json!({
+            "type": "object",
+            "properties": { "version": { "type": "integer" } },
+            "required": ["version"],
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let app = pg_app(&test, &[("SCHEMAS_DIR", dir.to_str().unwrap())]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let (status, _) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "deploy", "data": { "version": 3 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "deploy", "data": { "version": "3" } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "schema_mismatch");
+    assert!(!body["errors"].as_array().unwrap().is_empty());
+
+    let (status, _) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "other", "data": "anything" })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn patches_are_validated_against_the_schema_for_the_final_name() {
+    let Some(test) = test_db().await else { return };
+    let dir = env::temp_dir().join(format!("logger-schemas-{}", Uuid::new_v4()));
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("deploy.json"),
+        json!({
+            "type": "object",
+            "properties": { "version": { "type": "integer" } },
+            "required": ["version"],
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let app = pg_app(&test, &[("SCHEMAS_DIR", dir.to_str().unwrap())]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let body = post_logs(
+        &app,
+        json!([
+            { "name": "deploy", "data": { "version": 3 } },
+            { "name": "other", "data": "anything" },
+        ]),
+    )
+    .await;
+    let deploy = body["ids"][0].as_str().unwrap();
+    let other = body["ids"][1].as_str().unwrap();
+    let patch = |id: &str, body| json_request(Method::PATCH, &format!("/v1/log/{id}"), body);
+
+    let (status, body) =
+        send_json(&app, patch(deploy, json!({ "data": { "version": "4" } }))).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "schema_mismatch");
+    assert_eq!(body["errors"][0]["index"], 0);
+    assert_eq!(body["errors"][0]["path"], "/version");
+
+    let (status, body) = send_json(&app, patch(other, json!({ "name": "deploy" }))).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "schema_mismatch");
+
+    let (status, body) = send_json(&app, patch(deploy, json!({ "data": { "version": 4 } }))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["data"], json!({ "version": 4 }));
+
+    let (_, body) = send_json(&app, get_request(&format!("/v1/log/{other}"))).await;
+
+    assert_eq!(body["data"]["name"], "other");
+}
+
+#[tokio::test]
+async fn search_matches_data_containing_the_query() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "hit", "data": { "message": "disk full on /var" } },
+            { "name": "miss", "data": { "message": "all good" } },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/logs/search?q=disk%20full")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(log_names(&body), ["hit"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/logs/search?q=nowhere")).await;
+
+    assert!(log_names(&body).is_empty());
+}
+
+#[tokio::test]
+async fn tenants_only_see_their_own_logs() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("TENANT_KEYS", "acme:acme-key,globex:globex-key")]);
+
+    let acme = json!({ "name": "acme-log", "data": 1 });
+    let (_, stored) = send_json(&app, with_key(post_request("/v1/log", acme), "acme-key")).await;
+    let globex = json!({ "name": "globex-log", "data": 2 });
+
+    send_json(
+        &app,
+        with_key(post_request("/v1/log", globex), "globex-key"),
+    )
+    .await;
+
+    let (_, body) = send_json(&app, with_key(get_request("/v1/giveme"), "acme-key")).await;
+
+    assert_eq!(log_names(&body), ["acme-log"]);
+
+    let (_, body) = send_json(&app, with_key(get_request("/v1/giveme"), "globex-key")).await;
+
+    assert_eq!(log_names(&body), ["globex-log"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert!(log_names(&body).is_empty());
+
+    let uri = format!("/v1/log/{}", stored["id"].as_str().unwrap());
+    let (status, _) = send_json(&app, with_key(get_request(&uri), "globex-key")).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let delete = with_key(request(Method::DELETE, &uri, Body::empty()), "globex-key");
+    let (status, _) = send_json(&app, delete).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send_json(&app, with_key(get_request(&uri), "acme-key")).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn only_admin_keys_reach_admin_endpoints() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(
+        &test,
+        &[("KEYS", "admin-key"), ("TENANT_KEYS", "acme:acme-key")],
+    );
+    let toggle = || post_request("/v1/admin/readonly", json!({ "read_only": false }));
+
+    let (status, body) = send_json(&app, with_key(toggle(), "acme-key")).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+
+    let (status, body) = send_json(&app, with_key(toggle(), "admin-key")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[test]
+fn tenant_keys_cannot_claim_the_default_tenant() {
+    let Err(ConfigError(errors)) =
+        Config::from_env(&settings(&[("TENANT_KEYS", "default:sneaky-key")]))
+    else {
+        panic!("the default tenant was accepted in TENANT_KEYS");
+    };
+
+    assert!(
+        errors.iter().any(|err| err.contains("reserved")),
+        "{errors:?}"
+    );
+}
+
+fn signed_request(signed: &str, sent: &str, timestamp: i64) -> Request {
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"hmac-secret").unwrap();
+
+    mac.update(format!("{timestamp}.{signed}").as_bytes());
+
+    let mut request = request(Method::POST, "/v1/log", Body::from(sent.to_string()));
+    let headers = request.headers_mut();
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+    headers.insert(
+        SIGNATURE_HEADER,
+        HeaderValue::from_str(&hex::encode(mac.finalize().into_bytes())).unwrap(),
+    );
+
+    request
+}
+
+#[tokio::test]
+async fn signed_logs_need_a_fresh_matching_signature() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("HMAC_SECRET", "hmac-secret")]);
+    let body = r#"{"name":"signed","data":1}"#;
+    let now = chrono::Utc::now().timestamp();
+
+    let (status, _) = send_json(&app, signed_request(body, body, now)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let tampered = r#"{"name":"signed","data":2}"#;
+    let (status, rejected) = send_json(&app, signed_request(body, tampered, now)).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(rejected["code"], "unauthorized");
+
+    let stale = now - 3600;
+    let (status, rejected) = send_json(&app, signed_request(body, body, stale)).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(rejected["message"], "Stale X-Timestamp");
+
+    let (_, count) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(count["count"], 1);
+}
+
+#[tokio::test]
+async fn log_echoes_the_stored_record_when_asked() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let data = json!({ "user": 1, "tags": ["a", "b"] });
+    let before = chrono::Utc::now();
+
+    let (status, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log?return=representation",
+            json!({ "name": "echo", "data": data }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "echo");
+    assert_eq!(body["data"]["data"], data);
+
+    let created: chrono::DateTime<chrono::Utc> =
+        body["data"]["created"].as_str().unwrap().parse().unwrap();
+
+    assert!(created >= before - chrono::Duration::seconds(1));
+    assert!(created <= chrono::Utc::now());
+
+    let mut request = post_request("/v1/log", json!({ "name": "echo", "data": 2 }));
+
+    request.headers_mut().insert(
+        HeaderName::from_static(PREFER_HEADER),
+        HeaderValue::from_static("return=representation"),
+    );
+
+    let (_, body) = send_json(&app, request).await;
+
+    assert_eq!(body["data"]["data"], 2);
+}
+
+#[tokio::test]
+async fn client_created_timestamps_are_stored_and_far_future_ones_rejected() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "backfilled", "data": 1, "created": "2023-06-01T08:30:00Z" },
+            { "name": "current", "data": 2 },
+        ]),
+    )
+    .await;
+
+    let uri = "/v1/giveme?created_after=2023-06-01T00:00:00Z&created_before=2023-06-02T00:00:00Z";
+    let (_, body) = send_json(&app, get_request(uri)).await;
+
+    assert_eq!(log_names(&body), ["backfilled"]);
+    assert_eq!(body["data"][0]["created"], "2023-06-01T08:30:00Z");
+
+    let future = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+    let (status, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log",
+            json!({ "name": "future", "data": 3, "created": future }),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn soft_deleted_logs_can_be_restored_until_hard_deleted() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let (_, stored) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "undo", "data": 1 })),
+    )
+    .await;
+    let id = stored["id"].as_str().unwrap();
+    let uri = format!("/v1/log/{id}");
+    let restore = format!("/v1/log/{id}/restore");
+
+    send_json(&app, request(Method::DELETE, &uri, Body::empty())).await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert!(log_names(&body).is_empty());
+
+    let (status, _) = send_json(&app, request(Method::POST, &restore, Body::empty())).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body), ["undo"]);
+
+    let hard = format!("{uri}?hard=true");
+    let (status, _) = send_json(&app, request(Method::DELETE, &hard, Body::empty())).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send_json(&app, request(Method::POST, &restore, Body::empty())).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+
+    let rows = client.query("SELECT id FROM logs", &[]).await.unwrap();
+
+    assert!(rows.is_empty());
+}
+
+#[tokio::test]
+async fn pretty_responses_are_indented() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let compact = body_bytes(send(&app, get_request("/v1/count")).await).await;
+
+    assert!(!compact.contains(&b'\n'));
+
+    let pretty = body_bytes(send(&app, get_request("/v1/count?pretty=true")).await).await;
+    let pretty = String::from_utf8(pretty).unwrap();
+
+    assert!(pretty.contains("\n  \""), "{pretty}");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+        serde_json::from_slice::<serde_json::Value>(&compact).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn matching_etag_returns_not_modified() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(&app, json!([{ "name": "cached", "data": 1 }])).await;
+
+    let response = send(&app, get_request("/v1/giveme")).await;
+    let etag = response.headers()[header::ETAG].clone();
+    let mut request = get_request("/v1/giveme");
+
+    request
+        .headers_mut()
+        .insert(header::IF_NONE_MATCH, etag.clone());
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(body_bytes(response).await.is_empty());
+
+    post_logs(&app, json!([{ "name": "cached", "data": 2 }])).await;
+
+    let mut request = get_request("/v1/giveme");
+
+    request.headers_mut().insert(header::IF_NONE_MATCH, etag);
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn config_file_is_read_and_env_vars_override_it() {
+    let _lock = ENV_LOCK.lock().await;
+    let path = env::temp_dir().join(format!("logger-config-{}.toml", Uuid::new_v4()));
+
+    std::fs::write(
+        &path,
+        "key = \"file-key\"\nlimit = 25\nkeys = [\"one\", \"two\"]\nhmac_max_age_secs = 60\n",
+    )
+    .unwrap();
+    env::set_var("CONFIG_PATH", &path);
+    env::set_var("HMAC_MAX_AGE_SECS", "120");
+
+    let config = Settings::load().map(|settings| Config::from_env(&settings));
+
+    env::remove_var("CONFIG_PATH");
+    env::remove_var("HMAC_MAX_AGE_SECS");
+    std::fs::remove_file(&path).unwrap();
+
+    let config = config.unwrap().unwrap();
+
+    assert_eq!(config.limit, 25);
+    assert_eq!(config.keys.len(), 2);
+    assert_eq!(config.signature_max_age, Duration::from_secs(120));
+}
+
+#[tokio::test]
+async fn bulk_delete_removes_only_the_named_logs() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "noise", "data": 1 },
+            { "name": "noise", "data": 2 },
+            { "name": "signal", "data": 3 },
+        ]),
+    )
+    .await;
+
+    let delete = request(Method::DELETE, "/v1/logs?name=noise", Body::empty());
+    let (status, body) = send_json(&app, delete).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deleted"], 2);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body), ["signal"]);
+}
+
+#[tokio::test]
+async fn created_is_returned_as_rfc3339_or_epoch_ms() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([{ "name": "clock", "data": 1, "created": "2024-01-02T03:04:05.678Z" }]),
+    )
+    .await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(body["data"][0]["created"], "2024-01-02T03:04:05.678Z");
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?time_format=epoch_ms")).await;
+
+    assert_eq!(body["data"][0]["created"], 1_704_164_645_678_i64);
+
+    let (status, _) = send_json(&app, get_request("/v1/giveme?time_format=julian")).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn failing_row_rolls_back_the_whole_batch() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let batch = json!([
+        { "name": "first", "data": 1 },
+        { "name": "broken", "data": "nul \u{0000} byte" },
+        { "name": "last", "data": 3 },
+    ]);
+
+    let (status, body) = send_json(&app, post_request("/v1/log/batch", batch)).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 0);
+}
+
+#[tokio::test]
+async fn connection_errors_are_unavailable_and_query_errors_internal() {
+    let Some(test) = test_db().await else { return };
+    let unreachable = Arc::new(test_pool("postgres://postgres@127.0.0.1:1/logger", 1));
+    let app = build_app(Arc::new(pg_state(&unreachable, &[])));
+
+    let response = send(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        response.headers()[header::RETRY_AFTER],
+        UNAVAILABLE_RETRY_AFTER
+    );
+    assert_eq!(body_json(response).await["code"], "db_unavailable");
+
+    let closed = QueryError::Database(diesel::result::Error::DatabaseError(
+        DatabaseErrorKind::ClosedConnection,
+        Box::new("server closed the connection".to_string()),
+    ));
+    let response = query_error(closed, "Could not get data").into_response();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let conn = test.db.get().await.unwrap();
+    let invalid = interact(conn, Duration::from_secs(5), |conn| {
+        diesel::sql_query("SELECT * FROM missing_table").execute(conn)
+    })
+    .await
+    .unwrap()
+    .unwrap_err();
+    let response = query_error(invalid.into(), "Could not get data").into_response();
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!response.headers().contains_key(header::RETRY_AFTER));
+    assert_eq!(body_json(response).await["message"], "Could not get data");
+}
+
+#[tokio::test]
+async fn unversioned_routes_work_and_are_marked_deprecated() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let response = send(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key(DEPRECATION_HEADER));
+
+    let response = send(&app, get_request("/giveme")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[DEPRECATION_HEADER], "true");
+    assert_eq!(
+        response.headers()[header::LINK],
+        "</v1/giveme>; rel=\"successor-version\""
+    );
+}
+
+#[tokio::test]
+async fn openapi_spec_lists_log_and_giveme() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, spec) = send_json(&app, without_key(get_request("/openapi.json"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(spec["paths"]["/log"]["post"].is_object());
+    assert!(spec["paths"]["/giveme"]["get"].is_object());
+}
+
+#[tokio::test]
+async fn head_count_returns_the_count_header_without_a_body() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([{ "name": "a", "data": 1 }, { "name": "b", "data": 2 }]),
+    )
+    .await;
+
+    for uri in ["/v1/count", "/v1/giveme"] {
+        let response = send(&app, request(Method::HEAD, uri, Body::empty())).await;
+
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        assert_eq!(response.headers()[TOTAL_COUNT_HEADER], "2", "{uri}");
+        assert!(body_bytes(response).await.is_empty(), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn duplicate_payloads_are_deduped_and_distinct_ones_inserted() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let log = json!({ "name": "dup", "data": { "n": 1 } });
+
+    let (_, first) = send_json(&app, post_request("/v1/log?dedupe=true", log.clone())).await;
+
+    assert_eq!(first["deduped"], false);
+
+    let (_, second) = send_json(&app, post_request("/v1/log?dedupe=true", log)).await;
+
+    assert_eq!(second["deduped"], true);
+    assert_eq!(second["id"], first["id"]);
+
+    let (_, third) = send_json(
+        &app,
+        post_request(
+            "/v1/log?dedupe=true",
+            json!({ "name": "dup", "data": { "n": 2 } }),
+        ),
+    )
+    .await;
+
+    assert_eq!(third["deduped"], false);
+    assert_ne!(third["id"], first["id"]);
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 2);
+}
+
+#[tokio::test]
+async fn index_reports_version_and_uptime() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let (status, body) = send_json(&app, without_key(get_request("/"))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], env!("CARGO_PKG_NAME"));
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["uptime_secs"].is_u64());
+}
+
+#[tokio::test]
+async fn malformed_and_incomplete_bodies_are_told_apart() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let json_body = |body: &'static str| {
+        let mut request = request(Method::POST, "/v1/log", Body::from(body));
+
+        request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+
+        request
+    };
+
+    let (status, body) = send_json(&app, json_body(r#"{"name": "broken", "data": "#)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_json");
+
+    let (status, body) = send_json(&app, json_body(r#"{"data": 1}"#)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_body");
+    assert!(body["message"].as_str().unwrap().contains("name"), "{body}");
+}
+
+#[tokio::test]
+async fn deletes_are_recorded_in_the_audit_log() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let (_, stored) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "audited", "data": 1 })),
+    )
+    .await;
+    let id = stored["id"].as_str().unwrap();
+
+    send_json(
+        &app,
+        request(Method::DELETE, &format!("/v1/log/{id}"), Body::empty()),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/audit")).await;
+    let entry = &body["data"][0];
+    let key_hash = hex::encode(Sha256::digest(TEST_KEY));
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(entry["affected"], 1);
+    assert_eq!(entry["ids"], json!([id]));
+    assert_eq!(entry["key_hash"], key_hash);
+    assert!(
+        entry["operation"].as_str().unwrap().contains("delete"),
+        "{entry}"
+    );
+}
+
+#[test]
+fn default_and_short_keys_are_refused_in_production_and_flagged_in_dev() {
+    for key in [DEFAULT_KEY, "short-key"] {
+        let Err(ConfigError(errors)) =
+            Config::from_env(&settings(&[("KEY", key), ("APP_ENV", "production")]))
+        else {
+            panic!("the weak key {key} was accepted in production");
+        };
+
+        assert!(
+            errors.iter().any(|err| err.contains("insecure default")),
+            "{errors:?}"
+        );
+    }
+
+    let config = Config::from_env(&settings(&[
+        ("KEYS", "a-long-enough-admin-key"),
+        ("APP_ENV", "production"),
+    ]))
+    .unwrap();
+
+    assert!(!config.weak_keys);
+
+    let captured = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer({
+            let captured = captured.clone();
+            move || captured.clone()
+        })
+        .finish();
+    let config = tracing::subscriber::with_default(subscriber, || {
+        Config::from_env(&settings(&[("KEY", DEFAULT_KEY)]))
+    });
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+
+    assert!(config.unwrap().weak_keys);
+    assert!(!logs.contains("WARN"), "{logs}");
+}
+
+#[tokio::test]
+async fn plaintext_logs_are_stored_as_a_message() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let mut request = request(
+        Method::POST,
+        "/v1/log/text?name=syslog&level=warn",
+        Body::from("disk almost full"),
+    );
+
+    request
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+    let (status, _) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(body["data"][0]["name"], "syslog");
+    assert_eq!(body["data"][0]["level"], "warn");
+    assert_eq!(
+        body["data"][0]["data"],
+        json!({ "message": "disk almost full" })
+    );
+}
+
+async fn export_page(app: &Router, uri: &str) -> (Vec<String>, Option<String>) {
+    let response = send(app, get_request(uri)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let token = response
+        .headers()
+        .get(RESUME_TOKEN_HEADER)
+        .map(|token| token.to_str().unwrap().to_string());
+    let body = String::from_utf8(body_bytes(response).await).unwrap();
+    let ids = body
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+
+    (ids, token)
+}
+
+#[tokio::test]
+async fn export_resumes_without_duplicates() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let tied = minutes_ago(5);
+    let logs: Vec<_> = (0..5)
+        .map(|n| json!({ "name": "backup", "data": n, "created": if n < 3 { tied.clone() } else { minutes_ago(n) } }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let (first, token) = export_page(&app, "/v1/export?limit=3").await;
+    let token = token.unwrap();
+    let (second, token) = export_page(&app, &format!("/v1/export?after={token}")).await;
+    let mut all = [first.clone(), second.clone()].concat();
+
+    assert_eq!(first.len(), 3);
+    assert_eq!(second.len(), 2);
+
+    all.sort();
+    all.dedup();
+
+    assert_eq!(all.len(), 5);
+
+    let (rest, _) = export_page(&app, &format!("/v1/export?after={}", token.unwrap())).await;
+
+    assert!(rest.is_empty());
+}
+
+#[derive(QueryableByName)]
+struct BackendPid {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pid: i32,
+}
+
+#[tokio::test]
+async fn queries_succeed_right_after_a_dropped_connection() {
+    let Some(test) = test_db().await else { return };
+    let db = Arc::new(test_pool(&test.url, 1));
+    let app = build_app(Arc::new(pg_state(&db, &[])));
+    let conn = db.get().await.unwrap();
+    let backend = conn
+        .interact(|conn| {
+            diesel::sql_query("SELECT pg_backend_pid() AS pid").get_result::<BackendPid>(conn)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop(conn);
+
+    let (client, connection) = tokio_postgres::connect(&test.url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+    client
+        .execute("SELECT pg_terminate_backend($1)", &[&backend.pid])
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (status, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["count"], 0);
+}
+
+#[tokio::test]
+async fn quotas_reject_only_the_name_over_its_limit() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("QUOTAS", "metrics:2")]);
+
+    for n in 0..2 {
+        let (status, _) = send_json(
+            &app,
+            post_request("/v1/log", json!({ "name": "metrics", "data": n })),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "metrics", "data": 2 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "quota_exceeded");
+
+    let (status, _) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "events", "data": 3 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send_json(&app, get_request("/v1/count?name=metrics")).await;
+
+    assert_eq!(body["count"], 2);
+}
+
+#[tokio::test]
+async fn concurrent_writes_cannot_overshoot_a_quota() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("QUOTAS", "metrics:3")]);
+
+    let writes = (0..8)
+        .map(|n| {
+            let app = app.clone();
+
+            tokio::spawn(async move {
+                send_json(
+                    &app,
+                    post_request("/v1/log", json!({ "name": "metrics", "data": n })),
+                )
+                .await
+                .0
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut statuses = Vec::new();
+
+    for write in writes {
+        statuses.push(write.await.unwrap());
+    }
+
+    let accepted = statuses.iter().filter(|status| **status == StatusCode::OK);
+
+    assert_eq!(accepted.count(), 3, "{statuses:?}");
+    assert!(statuses
+        .iter()
+        .all(|status| *status == StatusCode::OK || *status == StatusCode::TOO_MANY_REQUESTS));
+
+    let (_, body) = send_json(&app, get_request("/v1/count?name=metrics")).await;
+
+    assert_eq!(body["count"], 3);
+}
+
+#[tokio::test]
+async fn soft_deleted_logs_count_toward_a_quota() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("QUOTAS", "metrics:2")]);
+    let body = post_logs(
+        &app,
+        json!([{ "name": "metrics", "data": 0 }, { "name": "metrics", "data": 1 }]),
+    )
+    .await;
+    let uri = format!("/v1/log/{}", body["ids"][0].as_str().unwrap());
+
+    let (status, _) = send_json(&app, request(Method::DELETE, &uri, Body::empty())).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(
+        &app,
+        post_request("/v1/log", json!({ "name": "metrics", "data": 2 })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{body}");
+    assert_eq!(body["code"], "quota_exceeded");
+}
+
+#[tokio::test]
+async fn evicting_keeps_backdated_logs_from_the_same_batch() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("QUOTAS", "metrics:2"), ("QUOTA_ACTION", "evict")]);
+
+    post_logs(
+        &app,
+        json!([{ "name": "metrics", "data": "first" }, { "name": "metrics", "data": "second" }]),
+    )
+    .await;
+    post_logs(
+        &app,
+        json!([{ "name": "metrics", "data": "backdated", "created": minutes_ago(60) }]),
+    )
+    .await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme?name=metrics")).await;
+    let data = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| log["data"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(data.len(), 2, "{body}");
+    assert!(data.contains(&"backdated".to_string()), "{body}");
+
+    let (status, body) = send_json(
+        &app,
+        post_request(
+            "/v1/log/batch",
+            json!([
+                { "name": "metrics", "data": 1 },
+                { "name": "metrics", "data": 2 },
+                { "name": "metrics", "data": 3 },
+            ]),
+        ),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{body}");
+}
+
+#[tokio::test]
+async fn fields_projects_only_the_requested_columns() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([{ "name": "projected", "data": { "big": true } }]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/giveme?fields=id,created")).await;
+    let log = body["data"][0].as_object().unwrap();
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(log.contains_key("id"));
+    assert!(log.contains_key("created"));
+    assert!(!log.contains_key("name"));
+    assert!(!log.contains_key("data"));
+}
+
+#[tokio::test]
+async fn request_id_is_generated_or_preserved() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    let response = send(&app, get_request("/v1/count")).await;
+    let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+
+    assert!(Uuid::parse_str(generated).is_ok(), "{generated}");
+
+    let mut request = get_request("/v1/count");
+
+    request.headers_mut().insert(
+        HeaderName::from_static(REQUEST_ID_HEADER),
+        HeaderValue::from_static("trace-abc-123"),
+    );
+
+    let response = send(&app, request).await;
+
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "trace-abc-123");
+}
+
+fn gzipped_log(body: &serde_json::Value) -> Request {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    std::io::Write::write_all(&mut encoder, body.to_string().as_bytes()).unwrap();
+
+    let mut request = request(
+        Method::POST,
+        "/v1/log",
+        Body::from(encoder.finish().unwrap()),
+    );
+    let headers = request.headers_mut();
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+
+    request
+}
+
+#[tokio::test]
+async fn gzipped_bodies_are_decompressed_and_limited() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[("MAX_BODY_BYTES", "1024")]);
+
+    let log = json!({ "name": "zipped", "data": { "ok": true } });
+    let (status, _) = send_json(&app, gzipped_log(&log)).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(body["data"][0]["data"], json!({ "ok": true }));
+
+    let bomb = json!({ "name": "zipped", "data": "a".repeat(64 * 1024) });
+    let (status, body) = send_json(&app, gzipped_log(&bomb)).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+
+    let (_, body) = send_json(&app, get_request("/v1/count")).await;
+
+    assert_eq!(body["count"], 1);
+}
+
+#[tokio::test]
+async fn tied_timestamps_are_ordered_by_id() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let tied = minutes_ago(1);
+    let logs: Vec<_> = (0..6)
+        .map(|n| json!({ "name": "tied", "data": n, "created": tied }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let ids = |body: serde_json::Value| -> Vec<String> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|log| log["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let (_, first) = send_json(&app, get_request("/v1/giveme")).await;
+    let (_, second) = send_json(&app, get_request("/v1/giveme")).await;
+    let first = ids(first);
+    let mut sorted = first.clone();
+
+    sorted.sort();
+    sorted.reverse();
+
+    assert_eq!(first, ids(second));
+    assert_eq!(first, sorted);
+
+    let (_, ascending) = send_json(&app, get_request("/v1/giveme?order=asc")).await;
+
+    sorted.reverse();
+
+    assert_eq!(ids(ascending), sorted);
+}
+
+#[tokio::test]
+async fn data_endpoint_returns_only_the_stored_data() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    for data in [json!([1, "two", null]), json!("plain"), json!(42)] {
+        let (_, stored) = send_json(
+            &app,
+            post_request("/v1/log", json!({ "name": "raw", "data": data })),
+        )
+        .await;
+        let uri = format!("/v1/log/{}/data", stored["id"].as_str().unwrap());
+        let (status, body) = send_json(&app, get_request(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, data);
+    }
+
+    let missing = format!("/v1/log/{}/data", Uuid::new_v4());
+    let (status, _) = send_json(&app, get_request(&missing)).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn missing_logs_table_fails_the_schema_check() {
+    let Some(test) = test_db().await else { return };
+    let fresh = FreshDb::create(&test).await;
+
+    let schema = check_schema(&fresh.db, DEFAULT_QUERY_TIMEOUT).await;
+
+    fresh.drop().await;
+
+    let err = schema.unwrap_err();
+
+    assert!(err.contains("relation \"logs\" does not exist"), "{err}");
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[tokio::test]
+async fn big_numbers_come_back_unchanged() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+    let numbers =
+        r#"[123456789012345678901234567890,9007199254740993,0.1000000000000000055511151231257827]"#;
+    let body = format!("{{\"name\":\"precise\",\"data\":{numbers}}}");
+    let mut request = request(Method::POST, "/v1/log", Body::from(body));
+
+    request.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    let (status, _) = send_json(&app, request).await;
+
+    assert_eq!(status, StatusCode::OK);
+
+    let response = send(&app, get_request("/v1/giveme")).await;
+    let body = String::from_utf8(body_bytes(response).await).unwrap();
+
+    assert!(body.contains(&format!("\"data\":{numbers}")), "{body}");
+}
+
+#[tokio::test]
+async fn reload_applies_a_changed_limit() {
+    let Some(test) = test_db().await else { return };
+    let _lock = ENV_LOCK.lock().await;
+    let path = env::temp_dir().join(format!("logger-reload-{}.toml", Uuid::new_v4()));
+    let app = pg_app(&test, &[("LIMIT", "5")]);
+    let logs: Vec<_> = (0..4)
+        .map(|n| json!({ "name": "reloaded", "data": n }))
+        .collect();
+
+    post_logs(&app, json!(logs)).await;
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body).len(), 4);
+
+    std::fs::write(&path, format!("key = \"{TEST_KEY}\"\nlimit = 2\n")).unwrap();
+    env::set_var("CONFIG_PATH", &path);
+
+    let (status, reloaded) = send_json(&app, post_request("/v1/admin/reload", json!({}))).await;
+
+    env::remove_var("CONFIG_PATH");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(status, StatusCode::OK, "{reloaded}");
+
+    let (_, body) = send_json(&app, get_request("/v1/giveme")).await;
+
+    assert_eq!(log_names(&body).len(), 2);
+    assert_eq!(body["meta"]["has_more"], true);
+}
+
+#[tokio::test]
+async fn latest_returns_the_newest_log_per_name() {
+    let Some(test) = test_db().await else { return };
+    let app = pg_app(&test, &[]);
+
+    post_logs(
+        &app,
+        json!([
+            { "name": "api", "data": "old", "created": minutes_ago(30) },
+            { "name": "api", "data": "new", "created": minutes_ago(10) },
+            { "name": "worker", "data": "new", "created": minutes_ago(20) },
+            { "name": "worker", "data": "old", "created": minutes_ago(40) },
+            { "name": "api", "data": "older", "created": minutes_ago(50) },
+        ]),
+    )
+    .await;
+
+    let (status, body) = send_json(&app, get_request("/v1/latest")).await;
+    let mut latest: Vec<_> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| (log["name"].as_str().unwrap(), log["data"].as_str().unwrap()))
+        .collect();
+
+    latest.sort();
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(latest, [("api", "new"), ("worker", "new")]);
+}
+
+#[tokio::test]
+async fn csv_export_fetches_in_chunks_without_holding_a_connection() {
+    let Some(test) = test_db().await else { return };
+    let rows = 1100;
+
+    assert!(rows > STREAM_CHUNK * 2);
+
+    execute(
+        &test.db,
+        "INSERT INTO logs (id, name, data, created) SELECT gen_random_uuid(), 'bulk', to_jsonb(n), now() - interval '1 hour' + n * interval '1 millisecond' FROM generate_series(1, 1100) n",
+    )
+    .await;
+
+    let db = Arc::new(test_pool(&test.url, 1));
+    let app = build_app(Arc::new(pg_state(&db, &[])));
+    let response = send(&app, get_request("/v1/export.csv")).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut frames = response.into_body().into_data_stream();
+    let header = frames.next().await.unwrap().unwrap();
+
+    assert_eq!(header, "id,name,data,created\n");
+
+    post_logs(&app, json!([{ "name": "late", "data": "after" }])).await;
+
+    let mut lines = Vec::new();
+
+    while let Some(frame) = frames.next().await {
+        lines.push(String::from_utf8(frame.unwrap().to_vec()).unwrap());
+    }
+
+    assert_eq!(lines.len() as i64, rows + 1);
+    assert!(lines[..rows as usize]
+        .iter()
+        .all(|line| line.contains(",\"bulk\",")));
+    assert!(lines[rows as usize].contains(",\"late\","));
+}